@@ -0,0 +1,193 @@
+//! BLOCK STATISTICS
+//!
+//! Walks every section of a dimension and counts each block state, broken down three ways: by Y
+//! level, by the biome of the block's 4x4x4 biome cell, and by chunk. Only chunks that finished
+//! generating are counted, proto-chunks haven't placed their ores yet and would skew the numbers.
+//! Chunks are decoded one at a time so the whole dimension never has to sit in memory.
+//!
+//! The CSV output is one row per count, with the breakdown it belongs to and its key:
+//!
+//! | breakdown --> | total | y      | biome            | chunk                    |
+//! |---------------|-------|--------|------------------|--------------------------|
+//! | key -->       | (none)| -59    | minecraft:plains | 3,-4 (chunk coordinates) |
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde_derive::Serialize;
+
+use crate::dimension::Dimension;
+use crate::region::{Region, RegionLoader};
+use crate::registry::BlockStateRegistry;
+use crate::source::join;
+use crate::workers::Progress;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Csv,
+    Json,
+}
+
+impl ReportFormat {
+    pub fn new(format: &str) -> Option<Self> {
+        match format.to_lowercase().as_str() {
+            "csv" => Some(ReportFormat::Csv),
+            "json" => Some(ReportFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct BlockStatistics {
+    pub chunks: usize,
+    pub total: BTreeMap<String, u64>,
+    pub by_y: BTreeMap<i32, BTreeMap<String, u64>>,
+    pub by_biome: BTreeMap<String, BTreeMap<String, u64>>,
+    /// Keyed by "x,z" chunk coordinates
+    pub by_chunk: BTreeMap<String, BTreeMap<String, u64>>,
+}
+
+impl BlockStatistics {
+    /// Count the blocks in every region file of a dimension's `region` directory. When block names
+    /// are given, e.g. "minecraft:diamond_ore", only states of those blocks are counted.
+    pub fn collect(dimension: &Dimension, blocks: &Vec<String>) -> Self {
+        let registry = Arc::new(BlockStateRegistry::new());
+        let mut statistics = BlockStatistics::default();
+
+        // counted by registry id first, names are only worked out once at the end
+        let mut total: HashMap<u32, u64> = HashMap::new();
+        let mut by_y: HashMap<(i32, u32), u64> = HashMap::new();
+        let mut by_biome: HashMap<(usize, u32), u64> = HashMap::new();
+        let mut biome_names: Vec<String> = vec![];
+        let mut by_chunk: HashMap<((i32, i32), u32), u64> = HashMap::new();
+
+        let dimension_type = Arc::new(dimension.dimension_type.clone());
+        let (min_section, max_section) = dimension_type.section_range();
+
+        let region_path = dimension.region_directory();
+        let mut region_files: Vec<String> = dimension.source.list(&region_path).into_iter()
+            .filter(|name| name.ends_with(".mca") && Region::coordinates(name).is_some())
+            .map(|name| join(&region_path, &name))
+            .collect();
+        dimension.source.sort_for_reading(&mut region_files);
+        if 0 == region_files.len() {
+            eprintln!("No region files found in {:?}", &region_path);
+            return statistics;
+        }
+
+        let progress = Progress::new("analyzed regions", region_files.len());
+        for file_path in &region_files {
+            let mut region = Region::open(file_path, registry.clone());
+            region.dimension_type = dimension_type.clone();
+            region.source = dimension.source.clone();
+
+            for chunk in region.into_chunks().filter(|chunk| 0 != chunk.data_version && !chunk.status.is_proto()) {
+                statistics.chunks += 1;
+
+                // the light-only sections above and below the world hold no blocks anyway
+                for section in chunk.sections.iter().filter(|section| min_section <= section.y && section.y <= max_section) {
+                    let palette = &section.block_states.palette;
+                    if 0 == palette.len() { continue }
+
+                    let counted: Vec<bool> = palette.iter()
+                        .map(|id| 0 == blocks.len() || blocks.contains(&registry.get(*id).name))
+                        .collect();
+                    if !counted.contains(&true) { continue }
+
+                    // biome names are swapped for an index once per section rather than per block
+                    let biomes: Vec<usize> = section.biomes.palette.iter()
+                        .map(|biome| match biome_names.iter().position(|name| name == biome) {
+                            Some(position) => position,
+                            None => {
+                                biome_names.push(biome.clone());
+                                biome_names.len() - 1
+                            }
+                        })
+                        .collect();
+
+                    for index in 0..4096 {
+                        let entry = match section.block_states.data[index] as usize {
+                            entry if entry < palette.len() => entry,
+                            _ => 0,
+                        };
+                        if !counted[entry] { continue }
+
+                        let id = palette[entry];
+                        let (x, y, z) = (index & 15, index >> 8, (index >> 4) & 15);
+                        let biome = section.biomes.data[((y >> 2) << 4) | ((z >> 2) << 2) | (x >> 2)] as usize;
+
+                        *total.entry(id).or_insert(0) += 1;
+                        *by_y.entry((section.y * 16 + y as i32, id)).or_insert(0) += 1;
+                        *by_chunk.entry(((chunk.x >> 4, chunk.z >> 4), id)).or_insert(0) += 1;
+                        if let Some(biome) = biomes.get(biome) {
+                            *by_biome.entry((*biome, id)).or_insert(0) += 1;
+                        }
+                    }
+                }
+            }
+
+            progress.advance();
+        }
+
+        let name = |id: u32| registry.get(id).to_string();
+
+        for (id, count) in total {
+            statistics.total.insert(name(id), count);
+        }
+        for ((y, id), count) in by_y {
+            statistics.by_y.entry(y).or_default().insert(name(id), count);
+        }
+        for ((biome, id), count) in by_biome {
+            statistics.by_biome.entry(biome_names[biome].clone()).or_default().insert(name(id), count);
+        }
+        for (((chunk_x, chunk_z), id), count) in by_chunk {
+            statistics.by_chunk.entry(format!("{},{}", chunk_x, chunk_z)).or_default().insert(name(id), count);
+        }
+
+        statistics
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("breakdown,key,block_state,count\n");
+
+        let mut write_rows = |breakdown: &str, key: &str, counts: &BTreeMap<String, u64>| {
+            for (block_state, count) in counts {
+                csv.push_str(&format!("{},{},{},{}\n", breakdown, csv_field(key), csv_field(block_state), count));
+            }
+        };
+
+        write_rows("total", "", &self.total);
+        for (y, counts) in &self.by_y { write_rows("y", &y.to_string(), counts) }
+        for (biome, counts) in &self.by_biome { write_rows("biome", biome, counts) }
+        for (chunk, counts) in &self.by_chunk { write_rows("chunk", chunk, counts) }
+
+        csv
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn write(&self, format: ReportFormat, output_path: &Path) {
+        let report = match format {
+            ReportFormat::Csv => self.to_csv(),
+            ReportFormat::Json => self.to_json(),
+        };
+
+        match fs::write(output_path, report) {
+            Ok(()) => println!("wrote block statistics for {:?} chunks to {:?}", self.chunks, output_path),
+            Err(err) => eprintln!("Error writing block statistics ({:?}): {err}", output_path),
+        }
+    }
+}
+
+/// Quote a CSV field if it needs it, block states list their properties with commas
+fn csv_field(value: &str) -> String {
+    match value.contains(',') || value.contains('"') {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => String::from(value),
+    }
+}
@@ -1,8 +1,25 @@
 use std::env;
+use std::process::exit;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Render,
+    Analyze,
+}
 
 #[derive(Debug)]
 pub struct Args {
+    pub command: Command,
     pub config_file: String,
+    /// World name from the config, or a path to a world directory
+    pub world: Option<String>,
+    pub dimension: String,
+    pub format: String,
+    pub output: Option<String>,
+    /// Only count these blocks, e.g. "minecraft:diamond_ore,minecraft:deepslate_diamond_ore"
+    pub blocks: Vec<String>,
+    /// Overrides the config's thread count
+    pub threads: Option<usize>,
 }
 
 pub trait ArgParse {
@@ -15,13 +32,54 @@ impl ArgParse for Args {
 
         // prepare struct with default values
         let mut args = Args {
+            command: Command::Render,
             config_file: String::from("config.toml"),
+            world: None,
+            dimension: String::from("overworld"),
+            format: String::from("csv"),
+            output: None,
+            blocks: vec![],
+            threads: None,
         };
 
+        // the subcommand, if any, comes first
+        if let Some(command) = arguments.get(1) {
+            if "analyze" == command {
+                args.command = Command::Analyze;
+            }
+        }
+
         // parse command line arguments
         for i in 0..arguments.len() {
-            if "--config" == arguments[i] {
-                args.config_file = String::from(&arguments[i + 1]);
+            let value = match arguments.get(i + 1) {
+                Some(value) => String::from(value),
+                None => continue,
+            };
+
+            match arguments[i].as_str() {
+                "--config" => args.config_file = value,
+                "--world" => args.world = Some(value),
+                "--dimension" => args.dimension = value,
+                "--format" => args.format = value,
+                "--output" => args.output = Some(value),
+                "--threads" => match value.parse::<usize>() {
+                    Ok(threads) => args.threads = Some(threads),
+                    Err(_) => {
+                        eprintln!("Error: invalid thread count {:?}, use a whole number, 0 uses every core", &value);
+                        exit(45)
+                    }
+                },
+                "--blocks" => {
+                    args.blocks = value.split(',')
+                        .map(|block| block.trim())
+                        .filter(|block| 0 < block.len())
+                        .map(|block| match block.contains(':') {
+                            true => String::from(block),
+                            false => format!("minecraft:{}", block),
+                        })
+                        .collect();
+                }
+                _ => {}
             }
         }
 
@@ -29,4 +87,4 @@ impl ArgParse for Args {
 
         args
     }
-}
\ No newline at end of file
+}
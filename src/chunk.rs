@@ -22,16 +22,96 @@
 //! next integer, thus the next index is shifted "to the right" by one bit. If the chunk section
 //! contains only a single block in the palette, the data tag is completely omitted.
 
+//! BLOCK ENTITIES
+//!
+//! Block entities hold the extra data a block state can't: sign text, container inventories,
+//! banner patterns and so on. Every block entity compound carries its `id`, its absolute block
+//! position and a `keepPacked` flag; everything else depends on the type of block entity. Types we
+//! don't recognize are still collected and their data is available in the raw compound.
+//!
+//! | TAG -->         |   id   | x / y / z | keepPacked | ... |
+//! |-----------------|--------|-----------|------------|-----|
+//! | TYPE -->        | String |    Int    |    Byte    | ... |
+
+//! CHUNK STATUS
+//!
+//! Chunks are generated in steps and saved between them, so the edge of the explored world is full
+//! of proto-chunks that stopped part way, e.g. at `minecraft:features`. Only chunks that reached
+//! `minecraft:full` are complete. 1.18 prefixed the names with the namespace, and chunks from before
+//! 1.14 used a different set of names altogether.
+//!
+//! | STATUS -->      | noise | carvers | features  | light   | spawn        | heightmaps | full                    |
+//! |-----------------|-------|---------|-----------|---------|--------------|------------|-------------------------|
+//! | LEGACY -->      | base  | carved  | decorated | lighted | mobs_spawned | finalized  | fullchunk/postprocessed |
+
+//! PROTO-CHUNK DATA
+//!
+//! Chunks that haven't finished generating carry a few extra bits of bookkeeping. `CarvingMasks`
+//! are bit sets of the blocks cave carvers have already dug out, indexed x | z << 4 | (y - min_y) << 8.
+//! `PostProcessing` is a list per section of packed x | y << 4 | z << 8 positions whose shape
+//! still needs updating, e.g. fences waiting to connect. Chunks generated next to terrain from
+//! before 1.18 get `blending_data` naming the sections that blend old terrain into new.
+//!
+//! | TAG -->         | CarvingMasks (AIR, LIQUID)      | PostProcessing     | blending_data (min/max_section) |
+//! |-----------------|---------------------------------|--------------------|---------------------------------|
+//! | TYPE -->        | Compound(Long Array/Byte Array) | List(List(Short))  | Compound(Int, Int)              |
+
+//! TILE TICKS
+//!
+//! Blocks and fluids with a pending scheduled update, e.g. redstone repeaters, observers or flowing
+//! water, are kept in the `block_ticks` and `fluid_ticks` lists so the update survives the chunk
+//! being unloaded. Each entry names the block or fluid, its absolute position, the number of ticks
+//! until it fires and a priority deciding the order of updates scheduled for the same tick.
+//!
+//! | TAG -->         |   i    |  p  |  t  | x / y / z |
+//! |-----------------|--------|-----|-----|-----------|
+//! | TYPE -->        | String | Int | Int |    Int    |
+
 use std::usize;
 use std::cmp::max;
-use std::collections::HashMap;
-use std::io::Read;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt;
+use std::io::{Read, Write};
 use std::process::exit;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use flate2::Compression;
 use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::ZlibEncoder;
+use log::warn;
+use sha2::{Digest, Sha256};
 
+use crate::dimension::DimensionType;
+use crate::item::Item;
 use crate::nbt::*;
+use crate::registry::BlockStateRegistry;
 use crate::tag::{Tag, TagType};
+use crate::version::MinecraftVersion;
+
+/// Root chunk tags that are parsed but only ever written back as they were read
+const RETAINED_TAGS: [&str; 16] = [
+    "DataVersion", "xPos", "yPos", "zPos", "Status", "LastUpdate", "InhabitedTime", "structures",
+    "entities", "Heightmaps", "Lights", "PostProcessing", "CarvingMasks", "block_ticks",
+    "fluid_ticks", "blending_data",
+];
+
+/// Stop on unknown tags instead of collecting them, meant for development against new versions
+static STRICT_PARSING: AtomicBool = AtomicBool::new(false);
+
+pub fn set_strict_parsing(strict: bool) {
+    STRICT_PARSING.store(strict, Ordering::Relaxed);
+}
+
+pub fn strict_parsing() -> bool {
+    STRICT_PARSING.load(Ordering::Relaxed)
+}
+
+/// Chunks from before 1.18 are only warned about once, a world of them would otherwise flood the log
+static LEGACY_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Unknown fields and statuses already warned about as (context, name), every chunk of a world carries the same ones
+static UNKNOWN_WARNED: Mutex<Option<HashSet<(String, String)>>> = Mutex::new(None);
 
 #[derive(Debug, Clone)]
 pub struct Chunk {
@@ -42,17 +122,87 @@ pub struct Chunk {
     pub x_pos: i32,
     pub z_pos: i32,
     pub y_pos: i32,
-    pub status: String,
+    pub status: ChunkStatus,
     pub last_update: i64,
     pub sections: Vec<ChunkSection>,
     pub block_entities: Vec<BlockEntity>,
     pub carving_masks: CarvingMask,
+    pub blending_data: Option<BlendingData>,
+    pub post_processing: Vec<PostProcessing>,
     pub heightmaps: Heightmap,
     pub lights: Vec<Vec<Box<str>>>,
+    pub is_light_on: bool,
+    /// Whether the light was worked out by relight rather than read from the chunk
+    pub relit: bool,
     pub fluid_ticks: Vec<TileTick>,
     pub block_ticks: Vec<TileTick>,
     pub inhabited_time: i64,
     pub structures: Vec<Structure>,
+    pub structure_references: Vec<StructureReference>,
+    pub extra: Tag,
+    /// Root tags that are written back untouched when the chunk is saved
+    pub retained: Tag,
+    /// Whether the chunk has been edited since it was loaded
+    pub modified: bool,
+    pub registry: Arc<BlockStateRegistry>,
+    pub dimension_type: Arc<DimensionType>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChunkStatus {
+    Empty,
+    StructureStarts,
+    StructureReferences,
+    Biomes,
+    Noise,
+    Surface,
+    Carvers,
+    LiquidCarvers,
+    Features,
+    InitializeLight,
+    Light,
+    Spawn,
+    Heightmaps,
+    Full,
+}
+
+impl ChunkStatus {
+    pub fn new(status: &str) -> Self {
+        let name = match status.split_once(':') {
+            Some((_, name)) => name,
+            None => status,
+        };
+
+        match name {
+            "structure_starts" => ChunkStatus::StructureStarts,
+            "structure_references" => ChunkStatus::StructureReferences,
+            "biomes" => ChunkStatus::Biomes,
+            "noise" | "base" => ChunkStatus::Noise,
+            "surface" => ChunkStatus::Surface,
+            "carvers" | "carved" => ChunkStatus::Carvers,
+            "liquid_carvers" | "liquid_carved" => ChunkStatus::LiquidCarvers,
+            "features" | "decorated" => ChunkStatus::Features,
+            "initialize_light" => ChunkStatus::InitializeLight,
+            "light" | "lighted" => ChunkStatus::Light,
+            "spawn" | "mobs_spawned" => ChunkStatus::Spawn,
+            "heightmaps" | "finalized" => ChunkStatus::Heightmaps,
+            "full" | "fullchunk" | "postprocessed" => ChunkStatus::Full,
+            "" | "empty" => ChunkStatus::Empty,
+            _ => {
+                // a step from a newer version, most likely, and the chunk is better drawn than lost
+                let mut warned = UNKNOWN_WARNED.lock().unwrap();
+                if warned.get_or_insert_with(HashSet::new).insert((String::from("status"), String::from(status))) {
+                    warn!("Unknown chunk status {:?}, treating those chunks as full", status);
+                }
+                ChunkStatus::Full
+            }
+        }
+    }
+
+    /// Anything short of full is a proto-chunk that's still waiting on generation
+    pub fn is_proto(&self) -> bool {
+        ChunkStatus::Full != *self
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -62,10 +212,13 @@ pub struct ChunkSection {
     pub biomes: Biomes,
     pub block_light: [u8; 4096],
     pub sky_light: [u8; 4096],
+    pub has_block_light: bool,
+    pub has_sky_light: bool,
+    pub extra: Tag,
 }
 
 impl Chunk {
-    pub fn new(bytes: Vec<u8>, x: i32, z: i32) -> Self {
+    pub fn new(bytes: Vec<u8>, x: i32, z: i32, registry: Arc<BlockStateRegistry>, dimension_type: Arc<DimensionType>) -> Self {
         let mut chunk = Chunk {
             data_version: 0,
             x: x,
@@ -74,17 +227,27 @@ impl Chunk {
             x_pos: 0,
             z_pos: 0,
             y_pos: 0,
-            status: String::new(),
+            status: ChunkStatus::Empty,
             last_update: 0,
             sections: vec![],
             block_entities: vec![],
             carving_masks: CarvingMask::new(),
+            blending_data: None,
+            post_processing: vec![],
             heightmaps: Heightmap::new(),
             lights: vec![vec![]],
+            is_light_on: false,
+            relit: false,
             fluid_ticks: vec![],
             block_ticks: vec![],
             inhabited_time: 0,
             structures: vec![],
+            structure_references: vec![],
+            extra: Tag::compound(""),
+            retained: Tag::compound(""),
+            modified: false,
+            registry,
+            dimension_type,
         };
 
         let raw_bytes = match bytes.len() {
@@ -99,7 +262,7 @@ impl Chunk {
         chunk
     }
 
-    fn decompress(bytes: Vec<u8>) -> Vec<u8> {
+    pub(crate) fn decompress(bytes: Vec<u8>) -> Vec<u8> {
         // get chunk size
         let size_bytes: [u8; 4] = [bytes[0], bytes[1], bytes[2], bytes[3]];
         let size: usize = u32::from_be_bytes(size_bytes) as usize;
@@ -139,19 +302,30 @@ impl Chunk {
 
     fn process_chunk(&mut self, nbt: NBT) -> &mut Self {
         let mut missing: Vec<String> = vec![];
+        let mut retained: Vec<Tag> = vec![];
+        let mut post_processing: Vec<Tag> = vec![];
         for tag in nbt.tags.subtags {
+            // sections, block entities and light are rebuilt from their parsed form when the
+            // chunk is written back, everything else we know of is kept as it was read
+            if RETAINED_TAGS.contains(&tag.name.as_str()) {
+                retained.push(tag.clone());
+            }
+
             match tag.name.as_str() {
                 "DataVersion" => self.data_version = tag.payload_int(),
                 "xPos" => self.x_pos = tag.payload_int(),
                 "yPos" => self.y_pos = tag.payload_int(),
                 "zPos" => self.z_pos = tag.payload_int(),
-                "Status" => self.status = tag.payload_string(),
+                "Status" => self.status = ChunkStatus::new(&tag.payload_string()),
                 "LastUpdate" => self.last_update = tag.payload_long(),
                 "sections" => {
-                    self.sections = Chunk::process_sections(tag.subtags);
+                    self.sections = Chunk::process_sections(tag.subtags, &self.registry);
                 }
                 "structures" => {
-                    self.structures = Chunk::process_structures(tag.subtags);
+                    let (structures, references, extra) = Chunk::process_structures(tag.subtags);
+                    self.structures = structures;
+                    self.structure_references = references;
+                    if 1 < extra.subtags.len() { self.extra.push(extra) }
                 }
                 "entities" => {
                     // println!("{:?}: {:?}", tag.name, tag.tagtype); exit(42069);
@@ -162,48 +336,77 @@ impl Chunk {
                 "Lights" => {
                     // println!("{:?}: {:?}", tag.name, tag.tagtype); exit(42069);
                 }
-                "isLightOn" => {
-                    // println!("{:?}: {:?}", tag.name, tag.tagtype); exit(42069);
-                }
-                "PostProcessing" => {
-                    // println!("{:?}: {:?}", tag.name, tag.tagtype); exit(42069);
-                }
-                "CarvingMasks" => {
-                    // println!("{:?}: {:?}", tag.name, tag.tagtype); exit(42069);
-                }
+                "isLightOn" => self.is_light_on = 0 != tag.payload_byte(),
+                "PostProcessing" => post_processing = tag.subtags,
+                "CarvingMasks" => self.carving_masks = Chunk::process_carving_masks(tag.subtags),
                 "block_entities" => {
-                    // println!("{:?}: {:?}", tag.name, tag.tagtype); exit(42069);
+                    self.block_entities = Chunk::process_block_entities(tag.subtags);
                 }
                 "block_ticks" => {
-                    // println!("{:?}: {:?}", tag.name, tag.tagtype); exit(42069);
+                    self.block_ticks = Chunk::process_tile_ticks(tag.subtags);
                 }
                 "fluid_ticks" => {
-                    // println!("{:?}: {:?}", tag.name, tag.tagtype); exit(42069);
+                    self.fluid_ticks = Chunk::process_tile_ticks(tag.subtags);
                 }
                 "InhabitedTime" => self.inhabited_time = tag.payload_long(),
-                "blending_data" => {
-                    for subtag in tag.subtags {
-                        // has subtags "max_section" and "min_section", both of payload int
-                    }
+                "blending_data" => self.blending_data = Some(Chunk::process_blending_data(tag.subtags)),
+                "Level" => {
+                    // chunks saved before 21w43a nest everything in here, kept but not read
+                    Chunk::report_legacy(self.data_version);
+                    self.extra.push(tag);
                 }
                 "" => {
                     // this is probably just an End tag
                 }
                 _ => {
-                    missing.push(tag.name);
+                    missing.push(tag.name.clone());
+                    self.extra.push(tag);
                 }
             }
         }
 
-        if 0 < missing.len() {
-            println!("Missing {:?} chunk fields: {:?}", missing.len(), missing);
-            exit(42069)
-        }
+        Chunk::report_unknown("chunk", &missing, 42069);
+
+        self.retained = Tag::compound_with("", retained);
+
+        // both are laid out from the bottom section up, which isn't known until yPos has been read
+        self.carving_masks.min_y = self.y_pos * 16;
+        self.post_processing = Chunk::process_post_processing(post_processing, self.x_pos, self.y_pos, self.z_pos);
 
         self
     }
 
-    fn process_sections(tags: Vec<Tag>) -> Vec<ChunkSection> {
+    fn report_legacy(data_version: i32) {
+        if LEGACY_WARNED.swap(true, Ordering::Relaxed) { return }
+
+        let saved_by = match MinecraftVersion::from_data_version(data_version) {
+            Some(version) if version.is_release() && version.matches_data_version(data_version) => format!("Minecraft {}", version),
+            Some(version) => format!("a {} snapshot", version),
+            None => format!("DataVersion {}", data_version),
+        };
+        warn!("Chunks saved by {} use the pre-1.18 layout and are skipped, open the world in 1.18 or newer to upgrade them", saved_by);
+    }
+
+    /// Unknown tags are a full stop when parsing strictly, otherwise they're kept and warned about
+    fn report_unknown(context: &str, missing: &Vec<String>, exit_code: i32) {
+        if 0 == missing.len() { return }
+
+        if strict_parsing() {
+            println!("Missing {:?} {} fields: {:?}", missing.len(), context, missing);
+            exit(exit_code)
+        }
+
+        let mut warned = UNKNOWN_WARNED.lock().unwrap();
+        let warned = warned.get_or_insert_with(HashSet::new);
+        let unwarned: Vec<&String> = missing.iter()
+            .filter(|field| warned.insert((context.to_string(), field.to_string())))
+            .collect();
+        if 0 == unwarned.len() { return }
+
+        warn!("Unknown {} fields kept as extra data: {:?}", context, unwarned);
+    }
+
+    fn process_sections(tags: Vec<Tag>, registry: &BlockStateRegistry) -> Vec<ChunkSection> {
         let mut sections = vec![];
 
         let mut missing: Vec<String> = vec![];
@@ -211,10 +414,13 @@ impl Chunk {
         for compound in tags {
             let mut section = ChunkSection {
                 y: 0,
-                block_states: BlockStates { palette: vec![], data: [0i16; 4096] },
-                biomes: Biomes { palette: vec![], data: [0u8; 64] },
+                block_states: BlockStates { palette: vec![], palette_extra: vec![], data: [0i16; 4096], extra: Tag::compound("") },
+                biomes: Biomes { palette: vec![], data: [0u8; 64], extra: Tag::compound("") },
                 block_light: [0u8; 4096],
                 sky_light: [0u8; 4096],
+                has_block_light: false,
+                has_sky_light: false,
+                extra: Tag::compound(""),
             };
 
             for tag in compound.subtags {
@@ -223,20 +429,23 @@ impl Chunk {
                         section.y = tag.payload_byte() as i32;
                     }
                     "block_states" => {
-                        section.block_states = Chunk::process_block_states(tag.subtags);
+                        section.block_states = Chunk::process_block_states(tag.subtags, registry);
                     }
                     "biomes" => {
                         section.biomes = Chunk::process_biomes(tag.subtags);
                     }
                     "BlockLight" => {
                         section.block_light = Chunk::process_lights(tag.payload_byte_array());
+                        section.has_block_light = true;
                     }
                     "SkyLight" => {
                         section.sky_light = Chunk::process_lights(tag.payload_byte_array());
+                        section.has_sky_light = true;
                     }
                     _ => {
                         if tag.tagtype != TagType::End {
-                            missing.push(tag.name)
+                            missing.push(tag.name.clone());
+                            section.extra.push(tag);
                         }
                     }
                 }
@@ -245,10 +454,7 @@ impl Chunk {
             sections.push(section);
         }
 
-        if 0 < missing.len() {
-            println!("Missing {:?} section fields: {:?}", missing.len(), missing);
-            exit(42069)
-        }
+        Chunk::report_unknown("section", &missing, 42069);
 
         sections
     }
@@ -256,29 +462,130 @@ impl Chunk {
 
 #[derive(Debug, Clone)]
 pub struct BlockStates {
-    pub palette: Vec<BlockState>,
+    /// Ids of the palette's block states in the world's block state registry
+    pub palette: Vec<u32>,
+    /// Fields of each palette entry we don't know about, kept in step with the palette and written
+    /// back out as they were
+    pub palette_extra: Vec<Tag>,
     pub data: [i16; 4096],
+    pub extra: Tag,
 }
 
-#[derive(Debug, Clone)]
+impl BlockStates {
+    /// The registry id of the block at an index within the section, y * 256 + z * 16 + x
+    pub fn id(&self, index: usize) -> u32 {
+        match self.palette.get(self.data[index] as usize) {
+            Some(id) => *id,
+            None => self.palette.first().copied().unwrap_or(0),
+        }
+    }
+
+    /// Point an index at a block state, growing the palette if the state is new to the section
+    pub fn set(&mut self, index: usize, id: u32) {
+        let entry = match self.palette.iter().position(|palette_id| *palette_id == id) {
+            Some(entry) => entry,
+            None => {
+                self.palette.push(id);
+                self.palette_extra.push(Tag::compound(""));
+                self.palette.len() - 1
+            }
+        };
+
+        self.data[index] = entry as i16;
+    }
+
+    /// The palette, with each entry's extra fields, without any entries that have fallen out of
+    /// use, and the packed data to go with it. The data is empty when there's only a single block
+    /// state left.
+    pub fn packed(&self) -> (Vec<(u32, Tag)>, Vec<i64>) {
+        let palette: Vec<(u32, Tag)> = self.palette.iter().enumerate()
+            .map(|(entry, id)| (*id, self.palette_extra.get(entry).cloned().unwrap_or_else(|| Tag::compound(""))))
+            .collect();
+        let entries: Vec<usize> = self.data.iter().map(|entry| *entry as usize).collect();
+        let (palette, entries) = shrink_palette(&palette, &entries);
+
+        let bits_per_entry = max(4, (palette.len() as f64).log2().ceil() as usize);
+        let data = match palette.len() {
+            0 | 1 => vec![],
+            _ => pack_entries(&entries, bits_per_entry),
+        };
+
+        (palette, data)
+    }
+}
+
+/// Drop unused and duplicate palette entries, returning the new palette and remapped entries
+fn shrink_palette<T: Clone + PartialEq>(palette: &Vec<T>, entries: &Vec<usize>) -> (Vec<T>, Vec<usize>) {
+    let mut shrunk: Vec<T> = vec![];
+    let mut remap: Vec<Option<usize>> = vec![None; palette.len()];
+
+    let entries = entries.iter().map(|entry| {
+        // out of range entries read as the first palette entry, so write them that way too
+        let entry = if *entry < palette.len() { *entry } else { 0 };
+        if palette.is_empty() { return 0 }
+
+        *remap[entry].get_or_insert_with(|| {
+            match shrunk.iter().position(|value| *value == palette[entry]) {
+                Some(position) => position,
+                None => {
+                    shrunk.push(palette[entry].clone());
+                    shrunk.len() - 1
+                }
+            }
+        })
+    }).collect();
+
+    (shrunk, entries)
+}
+
+/// Pack palette entries into longs, lowest bits first and never splitting an entry across longs
+fn pack_entries(entries: &Vec<usize>, bits_per_entry: usize) -> Vec<i64> {
+    let entries_per_long = 64 / bits_per_entry;
+
+    entries.chunks(entries_per_long)
+        .map(|entries| {
+            entries.iter().enumerate().fold(0u64, |long, (position, entry)| {
+                long | (*entry as u64) << (position * bits_per_entry)
+            }) as i64
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct BlockState {
     pub name: String,
-    pub properties: HashMap<String, String>,
+    pub properties: BTreeMap<String, String>,
+}
+
+impl fmt::Display for BlockState {
+    /// The same notation the game's commands use, e.g. "minecraft:oak_log[axis=y]"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if 0 < self.properties.len() {
+            let properties: Vec<String> = self.properties.iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            write!(f, "[{}]", properties.join(","))?;
+        }
+        Ok(())
+    }
 }
 
 trait BlockStateProcessor {
-    fn process_block_states(tags: Vec<Tag>) -> BlockStates;
-    fn process_block_state(tag: Tag) -> BlockState;
+    fn process_block_states(tags: Vec<Tag>, registry: &BlockStateRegistry) -> BlockStates;
+    fn process_block_state(tag: Tag) -> (BlockState, Tag);
     fn process_block_data(bits_per_entry: usize, long_ints: Vec<i64>) -> [i16; 4096];
 }
 
 impl BlockStateProcessor for Chunk {
-    fn process_block_states(tags: Vec<Tag>) -> BlockStates {
+    fn process_block_states(tags: Vec<Tag>, registry: &BlockStateRegistry) -> BlockStates {
         let mut missing: Vec<String> = vec![];
 
         let mut block_states = BlockStates {
             palette: vec![],
+            palette_extra: vec![],
             data: [0i16; 4096],
+            extra: Tag::compound(""),
         };
 
         // load up all palette block states
@@ -286,16 +593,16 @@ impl BlockStateProcessor for Chunk {
             match tag.name.as_str() {
                 "palette" => {
                     for compound_block_tag in tag.clone().subtags {
-                        block_states.palette.push(
-                            Chunk::process_block_state(compound_block_tag)
-                        )
+                        let (block_state, extra) = Chunk::process_block_state(compound_block_tag);
+                        block_states.palette.push(registry.intern(block_state));
+                        block_states.palette_extra.push(extra);
                     }
                 }
                 "data" => {} // ignore in case the palette hasn't fully loaded
                 _ => {
                     if tag.tagtype != TagType::End {
-                        println!("{:?}: {:?}", tag.name, tag.tagtype);
-                        missing.push(tag.clone().name)
+                        missing.push(tag.clone().name);
+                        block_states.extra.push(tag.clone());
                     }
                 }
             }
@@ -309,7 +616,7 @@ impl BlockStateProcessor for Chunk {
                     // calculate index length
                     let bits_per_entry = max(
                         4,
-                        (block_states.clone().palette.len() as f64).log2().ceil() as usize
+                        (block_states.palette.len() as f64).log2().ceil() as usize
                     );
 
                     // load block state data
@@ -335,21 +642,20 @@ impl BlockStateProcessor for Chunk {
             }
         }
 
-        if 0 < missing.len() {
-            println!("Missing {:?} block_state fields: {:?}", missing.len(), missing);
-            exit(535)
-        }
+        Chunk::report_unknown("block_states", &missing, 535);
 
         block_states
     }
 
-    fn process_block_state(tag: Tag) -> BlockState {
+    /// The block state of a palette entry, along with any fields of it we don't know about
+    fn process_block_state(tag: Tag) -> (BlockState, Tag) {
         let mut missing = vec![];
 
         let mut block_state = BlockState {
             name: "".to_string(),
             properties: Default::default(),
         };
+        let mut extra = Tag::compound("");
 
         for subtag in tag.subtags {
             match subtag.name.as_str() {
@@ -368,18 +674,16 @@ impl BlockStateProcessor for Chunk {
                 }
                 _ => {
                     if subtag.tagtype != TagType::End {
-                        missing.push(subtag.name)
+                        missing.push(subtag.name.clone());
+                        extra.push(subtag);
                     }
                 }
             }
         }
 
-        if 0 < missing.len() {
-            println!("Missing {:?} block_state fields: {:?}", missing.len(), missing);
-            exit(535)
-        }
+        Chunk::report_unknown("block_state", &missing, 535);
 
-        block_state
+        (block_state, extra)
     }
 
     fn process_block_data(bits_per_entry: usize, long_ints: Vec<i64>) -> [i16; 4096] {
@@ -432,8 +736,125 @@ impl BlockStateProcessor for Chunk {
 
 #[derive(Debug, Clone)]
 pub struct Biomes {
-    palette: Vec<String>,
-    data: [u8; 64],
+    pub palette: Vec<String>,
+    pub data: [u8; 64],
+    /// Fields we don't know about, written back out as they were
+    pub extra: Tag,
+}
+
+impl Biomes {
+    /// The biome of a 4x4x4 cell, using the cell's coordinates within the section (0-3 each)
+    pub fn biome(&self, x: usize, y: usize, z: usize) -> Option<&str> {
+        let index = self.data[(y << 4) | (z << 2) | x] as usize;
+        self.palette.get(index).map(|biome| biome.as_str())
+    }
+
+    /// Set the biome of a 4x4x4 cell, growing the palette if the biome is new to the section
+    pub fn set(&mut self, x: usize, y: usize, z: usize, biome: &str) {
+        let entry = match self.palette.iter().position(|name| name == biome) {
+            Some(entry) => entry,
+            None => {
+                self.palette.push(String::from(biome));
+                self.palette.len() - 1
+            }
+        };
+
+        self.data[(y << 4) | (z << 2) | x] = entry as u8;
+    }
+
+    /// The palette without any biomes that have fallen out of use, and the packed data to go with
+    /// it. Unlike block states there's no minimum number of bits per entry.
+    pub fn packed(&self) -> (Vec<String>, Vec<i64>) {
+        let entries: Vec<usize> = self.data.iter().map(|entry| *entry as usize).collect();
+        let (palette, entries) = shrink_palette(&self.palette, &entries);
+
+        let bits_per_entry = (palette.len() as f64).log2().ceil() as usize;
+        let data = match palette.len() {
+            0 | 1 => vec![],
+            _ => pack_entries(&entries, bits_per_entry),
+        };
+
+        (palette, data)
+    }
+
+    /// Vanilla never uses the world seed directly for biome zooming, only the first eight bytes of
+    /// the SHA-256 hash of it
+    pub fn zoom_seed(seed: i64) -> i64 {
+        let hash = Sha256::digest(seed.to_le_bytes());
+        i64::from_le_bytes(hash[0..8].try_into().unwrap())
+    }
+
+    /// Pick which 4x4x4 cell decides the biome of a block, the same way the client does.
+    ///
+    /// Rather than cutting the world into hard cubes, every block looks at the eight cells around
+    /// it and takes the nearest one after each cell has been nudged by a seeded random offset. This
+    /// is what gives biome borders their jagged look. Returns the chosen cell's coordinates.
+    pub fn zoom(zoom_seed: i64, x: i32, y: i32, z: i32) -> (i32, i32, i32) {
+        let (x, y, z) = (x - 2, y - 2, z - 2);
+        let (cell_x, cell_y, cell_z) = (x >> 2, y >> 2, z >> 2);
+        let (frac_x, frac_y, frac_z) = (
+            (x & 3) as f64 / 4.0,
+            (y & 3) as f64 / 4.0,
+            (z & 3) as f64 / 4.0,
+        );
+
+        let mut nearest = 0;
+        let mut nearest_distance = f64::INFINITY;
+
+        for corner in 0..8 {
+            let (low_x, low_y, low_z) = (0 == corner & 4, 0 == corner & 2, 0 == corner & 1);
+
+            let distance = Biomes::fiddled_distance(
+                zoom_seed,
+                if low_x { cell_x } else { cell_x + 1 },
+                if low_y { cell_y } else { cell_y + 1 },
+                if low_z { cell_z } else { cell_z + 1 },
+                if low_x { frac_x } else { frac_x - 1.0 },
+                if low_y { frac_y } else { frac_y - 1.0 },
+                if low_z { frac_z } else { frac_z - 1.0 },
+            );
+
+            if distance < nearest_distance {
+                nearest = corner;
+                nearest_distance = distance;
+            }
+        }
+
+        (
+            if 0 == nearest & 4 { cell_x } else { cell_x + 1 },
+            if 0 == nearest & 2 { cell_y } else { cell_y + 1 },
+            if 0 == nearest & 1 { cell_z } else { cell_z + 1 },
+        )
+    }
+
+    fn fiddled_distance(seed: i64, x: i32, y: i32, z: i32, frac_x: f64, frac_y: f64, frac_z: f64) -> f64 {
+        let mut random = Biomes::next_random(seed, x as i64);
+        random = Biomes::next_random(random, y as i64);
+        random = Biomes::next_random(random, z as i64);
+        random = Biomes::next_random(random, x as i64);
+        random = Biomes::next_random(random, y as i64);
+        random = Biomes::next_random(random, z as i64);
+        let fiddle_x = Biomes::fiddle(random);
+        random = Biomes::next_random(random, seed);
+        let fiddle_y = Biomes::fiddle(random);
+        random = Biomes::next_random(random, seed);
+        let fiddle_z = Biomes::fiddle(random);
+
+        (frac_z + fiddle_z).powi(2) + (frac_y + fiddle_y).powi(2) + (frac_x + fiddle_x).powi(2)
+    }
+
+    fn next_random(left: i64, right: i64) -> i64 {
+        // minecraft's linear congruential generator, overflow is expected
+        let left = left.wrapping_mul(
+            left.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407)
+        );
+        left.wrapping_add(right)
+    }
+
+    fn fiddle(random: i64) -> f64 {
+        let offset = (random >> 24).rem_euclid(1024) as f64 / 1024.0;
+        (offset - 0.5) * 0.9
+    }
 }
 
 trait BiomeProcessor {
@@ -448,6 +869,7 @@ impl BiomeProcessor for Chunk {
         let mut biomes = Biomes {
             palette: vec![],
             data: [0u8; 64],
+            extra: Tag::compound(""),
         };
 
         // load up all palette biomes
@@ -461,8 +883,8 @@ impl BiomeProcessor for Chunk {
                 "data" => {} // ignore in case the palette hasn't fully loaded
                 _ => {
                     if tag.tagtype != TagType::End {
-                        println!("{:?}: {:?}", &tag.name, &tag.tagtype);
-                        missing.push(tag.clone().name)
+                        missing.push(tag.clone().name);
+                        biomes.extra.push(tag.clone());
                     }
                 }
             }
@@ -496,10 +918,7 @@ impl BiomeProcessor for Chunk {
             }
         }
 
-        if 0 < missing.len() {
-            println!("Missing {:?} biome fields: {:?}", missing.len(), missing);
-            exit(42069)
-        }
+        Chunk::report_unknown("biome", &missing, 42069);
 
         biomes
     }
@@ -552,6 +971,510 @@ impl BiomeProcessor for Chunk {
     }
 }
 
+impl Chunk {
+    /// The section holding the given block y coordinate
+    pub fn section(&self, y: i32) -> Option<&ChunkSection> {
+        self.sections.iter().find(|section| section.y == y >> 4)
+    }
+
+    /// The biome at the given block coordinates, x and z may be absolute or within the chunk
+    pub fn biome_at(&self, x: i32, y: i32, z: i32) -> Option<&str> {
+        let section = self.section(y)?;
+        section.biomes.biome(((x & 15) >> 2) as usize, ((y & 15) >> 2) as usize, ((z & 15) >> 2) as usize)
+    }
+
+    /// Every biome cell in the column from the bottom of the chunk to the top, paired with the
+    /// lowest block y coordinate of the cell
+    pub fn biome_column(&self, x: i32, z: i32) -> impl Iterator<Item = (i32, &str)> + '_ {
+        let mut sections: Vec<&ChunkSection> = self.sections.iter().collect();
+        sections.sort_by_key(|section| section.y);
+
+        sections.into_iter().flat_map(move |section| {
+            (0..4).filter_map(move |cell_y| {
+                let biome = section.biomes.biome(((x & 15) >> 2) as usize, cell_y, ((z & 15) >> 2) as usize)?;
+                Some((section.y * 16 + cell_y as i32 * 4, biome))
+            })
+        })
+    }
+
+    /// The biome at the given block coordinates, blended between cells like the client does when
+    /// tinting grass and water. Cells outside this chunk are clamped to its edge, use the world's
+    /// lookup to blend across chunk borders.
+    pub fn smoothed_biome_at(&self, x: i32, y: i32, z: i32, zoom_seed: i64) -> Option<&str> {
+        let (cell_x, cell_y, cell_z) = Biomes::zoom(zoom_seed, self.x_pos * 16 + (x & 15), y, self.z_pos * 16 + (z & 15));
+
+        // keep the chosen cell inside this chunk
+        let min_section = self.sections.iter().map(|section| section.y).min()?;
+        let max_section = self.sections.iter().map(|section| section.y).max()?;
+        let cell_x = cell_x.clamp(self.x_pos * 4, self.x_pos * 4 + 3);
+        let cell_y = cell_y.clamp(min_section * 4, max_section * 4 + 3);
+        let cell_z = cell_z.clamp(self.z_pos * 4, self.z_pos * 4 + 3);
+
+        self.biome_at(cell_x * 4, cell_y * 4, cell_z * 4)
+    }
+}
+
+impl BlockState {
+    pub fn new(name: &str) -> Self {
+        BlockState {
+            name: String::from(name),
+            properties: BTreeMap::new(),
+        }
+    }
+
+    /// How much light the block gives off, 0 to 15
+    pub fn light_emission(&self) -> u8 {
+        let name = self.name.trim_start_matches("minecraft:");
+
+        // blocks that can be switched off
+        if Some("false") == self.properties.get("lit").map(|lit| lit.as_str()) {
+            return 0;
+        }
+
+        match name {
+            "light" => self.properties.get("level").and_then(|level| level.parse().ok()).unwrap_or(15),
+            // a cake only ever holds the one candle
+            _ if "candle_cake" == name || name.ends_with("_candle_cake") => 3,
+            _ if "candle" == name || name.ends_with("_candle") => {
+                let candles: u8 = self.properties.get("candles").and_then(|count| count.parse().ok()).unwrap_or(1);
+                3 * candles.min(4)
+            }
+            "sea_pickle" => match self.properties.get("waterlogged").map(|waterlogged| waterlogged.as_str()) {
+                Some("true") => {
+                    let pickles: u8 = self.properties.get("pickles").and_then(|count| count.parse().ok()).unwrap_or(1);
+                    3 + 3 * pickles
+                }
+                _ => 0,
+            },
+            "respawn_anchor" => {
+                let charges: u8 = self.properties.get("charges").and_then(|count| count.parse().ok()).unwrap_or(0);
+                [0, 3, 7, 11, 15][charges.min(4) as usize]
+            }
+            "lava" | "glowstone" | "sea_lantern" | "beacon" | "jack_o_lantern" | "shroomlight"
+            | "lantern" | "campfire" | "conduit" | "end_gateway" | "end_portal" | "fire"
+            | "redstone_lamp" | "ochre_froglight" | "verdant_froglight" | "pearlescent_froglight"
+            | "lava_cauldron" => 15,
+            _ if name.ends_with("copper_bulb") => 15,
+            "torch" | "wall_torch" | "end_rod" => 14,
+            _ if name.starts_with("cave_vines") => {
+                match self.properties.get("berries").map(|berries| berries.as_str()) {
+                    Some("true") => 14,
+                    _ => 0,
+                }
+            }
+            "furnace" | "blast_furnace" | "smoker" => 13,
+            "nether_portal" => 11,
+            "soul_torch" | "soul_wall_torch" | "soul_lantern" | "soul_fire" | "soul_campfire"
+            | "crying_obsidian" => 10,
+            "redstone_ore" | "deepslate_redstone_ore" => 9,
+            "enchanting_table" | "ender_chest" | "glow_lichen" | "redstone_torch"
+            | "redstone_wall_torch" => 7,
+            "amethyst_cluster" => 5,
+            "large_amethyst_bud" => 4,
+            "magma_block" => 3,
+            "medium_amethyst_bud" => 2,
+            "brewing_stand" | "brown_mushroom" | "dragon_egg" | "end_portal_frame"
+            | "sculk_sensor" | "calibrated_sculk_sensor" | "small_amethyst_bud" => 1,
+            _ => 0,
+        }
+    }
+
+    /// How much light the block takes away when light passes through it, 0 to 15
+    pub fn light_opacity(&self) -> u8 {
+        let name = self.name.trim_start_matches("minecraft:");
+
+        match name {
+            "air" | "cave_air" | "void_air" | "light" | "barrier" | "structure_void" => 0,
+            "water" | "bubble_column" | "ice" | "frosted_ice" | "cobweb" => 1,
+            _ if name.ends_with("_leaves") => 1,
+            // full blocks that happen to be see-through
+            _ if name.ends_with("glass") || name.ends_with("glass_pane") => 0,
+            "short_grass" | "grass" | "tall_grass" | "fern" | "large_fern" | "dead_bush" | "snow"
+            | "ladder" | "lever" | "cobblestone_wall" | "iron_bars" | "chain" | "scaffolding"
+            | "kelp" | "kelp_plant" | "seagrass" | "tall_seagrass" | "sugar_cane" | "bamboo"
+            | "wheat" | "carrots" | "potatoes" | "beetroots" | "sweet_berry_bush"
+            | "redstone_wire" | "repeater" | "comparator" | "tripwire" | "tripwire_hook"
+            | "brown_mushroom" | "red_mushroom" | "lily_pad" | "flower_pot" | "end_rod"
+            | "lantern" | "soul_lantern" | "bell" | "hopper" | "cauldron" | "anvil"
+            | "enchanting_table" | "brewing_stand" | "conduit" | "cactus" | "cake" => 0,
+            _ if name.ends_with("_block") => 15,
+            _ if ["torch", "sign", "rail", "door", "fence", "button", "pressure_plate", "carpet",
+                  "sapling", "tulip", "flower", "vine", "candle", "coral", "banner", "_bed", "head",
+                  "skull", "wall", "pickle", "roots", "fungus", "sprouts", "bud", "cluster",
+                  "dripleaf", "azalea", "lichen", "pointed_dripstone", "chest", "campfire",
+                  "orchid", "allium", "bluet", "daisy", "poppy", "dandelion", "cornflower",
+                  "lily_of_the_valley", "rose", "peony", "lilac", "sunflower", "frogspawn"]
+                .iter().any(|part| name.contains(part)) => 0,
+            _ => 15,
+        }
+    }
+}
+
+impl Chunk {
+    /// The registry id of the block state at the given block coordinates, x and z may be absolute
+    /// or within the chunk. Anything outside the stored sections is air.
+    pub fn block_id_at(&self, x: i32, y: i32, z: i32) -> u32 {
+        let index = (((y & 15) << 8) | ((z & 15) << 4) | (x & 15)) as usize;
+
+        match self.section(y) {
+            Some(section) => section.block_states.id(index),
+            None => 0,
+        }
+    }
+
+    pub fn block_at(&self, x: i32, y: i32, z: i32) -> Arc<BlockState> {
+        self.registry.get(self.block_id_at(x, y, z))
+    }
+
+    /// The block and sky light at the given block coordinates, x and z may be absolute or within
+    /// the chunk
+    pub fn light_at(&self, x: i32, y: i32, z: i32) -> (u8, u8) {
+        let index = (((y & 15) << 8) | ((z & 15) << 4) | (x & 15)) as usize;
+
+        let block_light = match self.section(y) {
+            Some(section) if section.has_block_light => section.block_light[index],
+            _ => 0,
+        };
+
+        let sky_light = match self.section(y) {
+            Some(section) if section.has_sky_light => section.sky_light[index],
+            // a missing sky light array is fully lit, unless something above it has light data
+            _ => match self.sections.iter().any(|section| y >> 4 < section.y && section.has_sky_light) {
+                true => 0,
+                false if self.dimension_type.has_skylight => 15,
+                false => 0,
+            },
+        };
+
+        (block_light, sky_light)
+    }
+
+    /// Whether the chunk was saved without usable light data and hasn't been relit since. Light is
+    /// only worked out when it's asked for, see World::light_at, since most chunks never need it.
+    pub fn needs_light(&self) -> bool {
+        if self.relit { return false }
+        !self.is_light_on || !self.sections.iter().any(|section| section.has_block_light || section.has_sky_light)
+    }
+
+    /// Work out block and sky light for the whole chunk from scratch.
+    ///
+    /// Sky light shines straight down from the top of the chunk until it hits something, and both
+    /// sky and block light then spread outwards losing at least one level per block. This only
+    /// looks at this chunk, so light coming in from neighbouring chunks is missed.
+    pub fn relight(&mut self) {
+        if 0 == self.sections.len() { return }
+
+        let min_section = self.sections.iter().map(|section| section.y).min().unwrap();
+        let max_section = self.sections.iter().map(|section| section.y).max().unwrap();
+        let height = ((max_section - min_section + 1) * 16) as usize;
+
+        // flatten the chunk into one column of opacity and emission values
+        let mut opacity = vec![0u8; 256 * height];
+        let mut block_light = vec![0u8; 256 * height];
+        let mut sky_light = vec![0u8; 256 * height];
+        for section in &self.sections {
+            let offset = ((section.y - min_section) * 16) as usize * 256;
+            let lights: Vec<(u8, u8)> = section.block_states.palette.iter()
+                .map(|id| self.registry.get(*id))
+                .map(|block_state| (block_state.light_opacity(), block_state.light_emission()))
+                .collect();
+            if 0 == lights.len() { continue }
+
+            for index in 0..4096 {
+                let (block_opacity, block_emission) = match lights.get(section.block_states.data[index] as usize) {
+                    Some(light) => *light,
+                    None => lights[0],
+                };
+                opacity[offset + index] = block_opacity;
+                block_light[offset + index] = block_emission;
+            }
+        }
+
+        // sky light pours straight down each column, in dimensions that have a sky
+        for column in (0..256).filter(|_| self.dimension_type.has_skylight) {
+            let mut light = 15u8;
+            for y in (0..height).rev() {
+                let index = y * 256 + column;
+                light = match opacity[index] {
+                    0 => light,
+                    block_opacity => light.saturating_sub(block_opacity),
+                };
+                sky_light[index] = light;
+                if 0 == light { break }
+            }
+        }
+
+        Chunk::spread_light(&mut block_light, &opacity, height);
+        Chunk::spread_light(&mut sky_light, &opacity, height);
+
+        // write it all back into the sections
+        for section in self.sections.iter_mut() {
+            let offset = ((section.y - min_section) * 16) as usize * 256;
+            section.block_light.copy_from_slice(&block_light[offset..offset + 4096]);
+            section.sky_light.copy_from_slice(&sky_light[offset..offset + 4096]);
+            section.has_block_light = true;
+            section.has_sky_light = true;
+        }
+
+        // is_light_on is left alone so the game still lights the chunk properly if it's saved
+        self.relit = true;
+    }
+
+    /// Flood light outwards from every lit block, indices are y * 256 + z * 16 + x
+    fn spread_light(light: &mut Vec<u8>, opacity: &Vec<u8>, height: usize) {
+        let mut queue: VecDeque<usize> = (0..light.len()).filter(|index| 1 < light[*index]).collect();
+
+        while let Some(index) = queue.pop_front() {
+            let (x, z, y) = (index % 16, (index / 16) % 16, index / 256);
+
+            let neighbours = [
+                (0 < x).then(|| index - 1),
+                (x < 15).then(|| index + 1),
+                (0 < z).then(|| index - 16),
+                (z < 15).then(|| index + 16),
+                (0 < y).then(|| index - 256),
+                (y + 1 < height).then(|| index + 256),
+            ];
+
+            for neighbour in neighbours.into_iter().flatten() {
+                let spread = light[index].saturating_sub(opacity[neighbour].max(1));
+                if light[neighbour] < spread {
+                    light[neighbour] = spread;
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+}
+
+impl Chunk {
+    /// Place a block state at the given block coordinates, x and z may be absolute or within the
+    /// chunk. Any block entity belonging to the old block is removed along with it.
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block_state: BlockState) {
+        if !self.dimension_type.contains_y(y) { return }
+
+        let id = self.registry.intern(block_state);
+        if id == self.block_id_at(x, y, z) { return }
+
+        let index = (((y & 15) << 8) | ((z & 15) << 4) | (x & 15)) as usize;
+        self.section_mut(y).block_states.set(index, id);
+
+        let (block_x, block_z) = (self.x + (x & 15), self.z + (z & 15));
+        self.block_entities.retain(|block_entity| {
+            !(block_entity.x == block_x && block_entity.y == y && block_entity.z == block_z)
+        });
+
+        self.invalidate();
+    }
+
+    /// Set the biome of the 4x4x4 cell holding the given block coordinates
+    pub fn set_biome(&mut self, x: i32, y: i32, z: i32, biome: &str) {
+        if !self.dimension_type.contains_y(y) { return }
+        if Some(biome) == self.biome_at(x, y, z) { return }
+
+        let section = self.section_mut(y);
+        section.biomes.set(((x & 15) >> 2) as usize, ((y & 15) >> 2) as usize, ((z & 15) >> 2) as usize, biome);
+
+        self.modified = true;
+    }
+
+    /// Fill the box between two corners (inclusive) with a block state
+    pub fn fill(&mut self, min: (i32, i32, i32), max: (i32, i32, i32), block_state: BlockState) {
+        let (min_x, max_x) = (min.0.max(self.x), max.0.min(self.x + 15));
+        let (min_z, max_z) = (min.2.max(self.z), max.2.min(self.z + 15));
+
+        for y in min.1..=max.1 {
+            for z in min_z..=max_z {
+                for x in min_x..=max_x {
+                    self.set_block(x, y, z, block_state.clone());
+                }
+            }
+        }
+    }
+
+    /// Swap every occurrence of one block state for another, returning how many blocks changed
+    pub fn replace_block(&mut self, from: &BlockState, to: BlockState) -> usize {
+        let from_id = match self.registry.id(from) {
+            Some(id) => id,
+            None => return 0, // never seen anywhere, so it can't be in this chunk
+        };
+        let to_id = self.registry.intern(to);
+        if from_id == to_id { return 0 }
+
+        let mut replaced = vec![];
+        for section in self.sections.iter_mut() {
+            // entries with their own extra fields can share a block state, so there may be several
+            let entries: Vec<usize> = section.block_states.palette.iter().enumerate()
+                .filter(|(_, id)| **id == from_id)
+                .map(|(entry, _)| entry)
+                .collect();
+            if 0 == entries.len() { continue }
+
+            // swapping the palette entry swaps every block using it in one go
+            for entry in &entries {
+                section.block_states.palette[*entry] = to_id;
+            }
+
+            for index in 0..4096 {
+                if entries.contains(&(section.block_states.data[index] as usize)) {
+                    replaced.push((index as i32 & 15, section.y * 16 + (index as i32 >> 8), (index as i32 >> 4) & 15));
+                }
+            }
+        }
+
+        if 0 == replaced.len() { return 0 }
+
+        let (chunk_x, chunk_z) = (self.x, self.z);
+        self.block_entities.retain(|block_entity| {
+            !replaced.contains(&(block_entity.x - chunk_x, block_entity.y, block_entity.z - chunk_z))
+        });
+
+        self.invalidate();
+
+        replaced.len()
+    }
+
+    /// The section holding the given block y coordinate, added as empty air if it's missing
+    fn section_mut(&mut self, y: i32) -> &mut ChunkSection {
+        if let Some(position) = self.sections.iter().position(|section| section.y == y >> 4) {
+            return &mut self.sections[position];
+        }
+
+        // borrow the biomes of the nearest section so the new one doesn't stand out
+        let biomes = match self.sections.iter().min_by_key(|section| (section.y - (y >> 4)).abs()) {
+            Some(section) => section.biomes.clone(),
+            None => Biomes { palette: vec![String::from("minecraft:plains")], data: [0u8; 64], extra: Tag::compound("") },
+        };
+
+        self.sections.push(ChunkSection {
+            y: y >> 4,
+            block_states: BlockStates { palette: vec![0], palette_extra: vec![Tag::compound("")], data: [0i16; 4096], extra: Tag::compound("") },
+            biomes,
+            block_light: [0u8; 4096],
+            sky_light: [0u8; 4096],
+            has_block_light: false,
+            has_sky_light: false,
+            extra: Tag::compound(""),
+        });
+        self.sections.sort_by_key(|section| section.y);
+
+        let position = self.sections.iter().position(|section| section.y == y >> 4).unwrap();
+        &mut self.sections[position]
+    }
+
+    /// Changed blocks make the stored heightmaps and light wrong, so drop them and let the game
+    /// work them out again the next time the chunk loads
+    fn invalidate(&mut self) {
+        self.retained.remove("Heightmaps");
+        self.is_light_on = false;
+        self.relit = false;
+        for section in self.sections.iter_mut() {
+            section.has_block_light = false;
+            section.has_sky_light = false;
+        }
+
+        self.modified = true;
+    }
+
+    /// Build the chunk's NBT back up from its parsed form, ready to be compressed into a region
+    pub fn to_nbt(&self) -> Tag {
+        let mut tags: Vec<Tag> = self.retained.subtags.clone();
+
+        tags.push(Tag::byte("isLightOn", self.is_light_on as i8));
+
+        let sections = self.sections.iter()
+            .map(|section| section.to_nbt(&self.registry, self.is_light_on))
+            .collect();
+        tags.push(Tag::list("sections", TagType::Compound, sections));
+
+        let block_entities = self.block_entities.iter()
+            .map(|block_entity| block_entity.raw.clone())
+            .collect();
+        tags.push(Tag::list("block_entities", TagType::Compound, block_entities));
+
+        // unknown tags go back in too, unless they were pulled out of something already retained
+        for tag in &self.extra.subtags {
+            if tags.iter().any(|existing| existing.name == tag.name) { continue }
+            tags.push(tag.clone());
+        }
+
+        Tag::compound_with("", tags)
+    }
+
+    /// The chunk as a region file payload: length, compression type and zlib compressed NBT
+    pub fn compress(&self) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(&self.to_nbt().bytes).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut payload = ((compressed.len() + 1) as u32).to_be_bytes().to_vec();
+        payload.push(2);
+        payload.extend(compressed);
+
+        payload
+    }
+}
+
+impl ChunkSection {
+    pub fn to_nbt(&self, registry: &BlockStateRegistry, with_light: bool) -> Tag {
+        let mut tags = vec![Tag::byte("Y", self.y as i8)];
+
+        if 0 < self.block_states.palette.len() {
+            let (palette, data) = self.block_states.packed();
+
+            let palette = palette.iter()
+                .map(|(id, extra)| {
+                    let block_state = registry.get(*id);
+                    let mut fields = vec![Tag::string("Name", &block_state.name)];
+                    if 0 < block_state.properties.len() {
+                        let properties = block_state.properties.iter()
+                            .map(|(name, value)| Tag::string(name, value))
+                            .collect();
+                        fields.push(Tag::compound_with("Properties", properties));
+                    }
+                    fields.extend(extra.subtags.iter().cloned());
+                    Tag::compound_with("", fields)
+                })
+                .collect();
+
+            let mut block_states = self.block_states.extra.subtags.clone();
+            block_states.push(Tag::list("palette", TagType::Compound, palette));
+            if 0 < data.len() { block_states.push(Tag::long_array("data", &data)) }
+
+            tags.push(Tag::compound_with("block_states", block_states));
+        }
+
+        if 0 < self.biomes.palette.len() {
+            let (palette, data) = self.biomes.packed();
+
+            let palette = palette.iter().map(|biome| Tag::string("", biome)).collect();
+
+            let mut biomes = self.biomes.extra.subtags.clone();
+            biomes.push(Tag::list("palette", TagType::String, palette));
+            if 0 < data.len() { biomes.push(Tag::long_array("data", &data)) }
+
+            tags.push(Tag::compound_with("biomes", biomes));
+        }
+
+        // light we worked out ourselves is only an estimate, so it's never saved
+        if with_light && self.has_block_light {
+            tags.push(Tag::byte_array("BlockLight", &ChunkSection::pack_light(&self.block_light)));
+        }
+        if with_light && self.has_sky_light {
+            tags.push(Tag::byte_array("SkyLight", &ChunkSection::pack_light(&self.sky_light)));
+        }
+
+        tags.extend(self.extra.subtags.iter().cloned());
+
+        Tag::compound_with("", tags)
+    }
+
+    /// Two light levels to a byte, the first block of the pair in the low bits
+    fn pack_light(light: &[u8; 4096]) -> Vec<u8> {
+        light.chunks(2).map(|pair| (pair[0] & 15) | (pair[1] & 15) << 4).collect()
+    }
+}
+
 trait LightProcessor {
     fn process_lights(byte_array: Vec<u8>) -> [u8; 4096];
 }
@@ -563,11 +1486,11 @@ impl LightProcessor for Chunk {
 
         // iterate over each byte
         for (index, &byte) in byte_array.iter().enumerate() {
-            // extract the first 4 bits
-            block_lights[index * 2] = (byte >> 4) & 0b00001111;
+            // the low 4 bits belong to the first block of the pair
+            block_lights[index * 2] = byte & 0b00001111;
 
-            // extract the last 4 bits
-            block_lights[index * 2 + 1] = byte & 0b00001111;
+            // the high 4 bits belong to the second
+            block_lights[index * 2 + 1] = (byte >> 4) & 0b00001111;
         }
 
         block_lights
@@ -576,55 +1499,124 @@ impl LightProcessor for Chunk {
 
 #[derive(Debug, Clone)]
 pub struct Structure {
-    structure_name: String,
-    x: i32,
-    z: i32,
+    pub structure_name: String,
+    pub x: i32,
+    pub z: i32,
+    pub bounding_box: Option<BoundingBox>,
+    pub children: Vec<StructurePiece>,
+    pub extra: Tag,
+}
+
+#[derive(Debug, Clone)]
+pub struct StructurePiece {
+    pub id: String,
+    pub bounding_box: Option<BoundingBox>,
+    pub template: Option<String>,
+    pub rotation: Option<String>,
+    pub extra: Tag,
+}
+
+#[derive(Debug, Clone)]
+pub struct StructureReference {
+    pub structure_name: String,
+    pub x: i32,
+    pub z: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_x: i32,
+    pub min_y: i32,
+    pub min_z: i32,
+    pub max_x: i32,
+    pub max_y: i32,
+    pub max_z: i32,
+}
+
+impl BoundingBox {
+    pub fn new(bounds: Vec<i32>) -> Option<Self> {
+        if 6 != bounds.len() { return None }
+
+        Some(BoundingBox {
+            min_x: bounds[0],
+            min_y: bounds[1],
+            min_z: bounds[2],
+            max_x: bounds[3],
+            max_y: bounds[4],
+            max_z: bounds[5],
+        })
+    }
+
+    /// The smallest box containing both boxes
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            min_z: self.min_z.min(other.min_z),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+            max_z: self.max_z.max(other.max_z),
+        }
+    }
+}
+
+impl Structure {
+    /// Group structure variants together, e.g. every "minecraft:village_*" is a "village"
+    pub fn category(&self) -> String {
+        let name = match self.structure_name.split_once(':') {
+            Some((_, name)) => name,
+            None => self.structure_name.as_str(),
+        };
+
+        match name {
+            _ if name.starts_with("village") => String::from("village"),
+            _ if name.starts_with("ruined_portal") => String::from("ruined_portal"),
+            _ if name.starts_with("shipwreck") => String::from("shipwreck"),
+            _ if name.starts_with("ocean_ruin") => String::from("ocean_ruin"),
+            _ => String::from(name),
+        }
+    }
 }
 
 trait StructureProcessor {
-    fn process_structures(structure_tags: Vec<Tag>) -> Vec<Structure>;
-    fn process_references(reference_tags: Vec<Tag>);
-    fn process_starts(starts_tags: Vec<Tag>);
-    fn process_children(children_tags: Vec<Tag>);
+    fn process_structures(structure_tags: Vec<Tag>) -> (Vec<Structure>, Vec<StructureReference>, Tag);
+    fn process_references(reference_tags: Vec<Tag>) -> Vec<StructureReference>;
+    fn process_starts(starts_tags: Vec<Tag>) -> Vec<Structure>;
+    fn process_children(children_tags: Vec<Tag>) -> Vec<StructurePiece>;
 }
 
 impl StructureProcessor for Chunk {
-    fn process_structures(structure_tags: Vec<Tag>) -> Vec<Structure> {
+    fn process_structures(structure_tags: Vec<Tag>) -> (Vec<Structure>, Vec<StructureReference>, Tag) {
         let mut structures = vec![];
+        let mut references = vec![];
+        let mut extra = Tag::compound("structures");
 
         let mut missing: Vec<String> = vec![];
 
         for structure in structure_tags {
-            // println!("{:?}: {:?}", structure.name, structure.tagtype);
             match structure.name.as_str() {
                 "References" => {
-                    // println!("{:?}", structure.subtags); exit(577);
-                    Chunk::process_references(structure.subtags);
+                    references = Chunk::process_references(structure.subtags);
                 }
                 "starts" => {
-                    // println!("{:?}", structure.subtags); //exit(580);
-                    Chunk::process_starts(structure.subtags);
+                    structures = Chunk::process_starts(structure.subtags);
                 }
                 _ => {
                     if structure.tagtype != TagType::End {
-                        missing.push(structure.name)
+                        missing.push(structure.name.clone());
+                        extra.push(structure);
                     }
                 }
             }
         }
 
-        if 0 < missing.len() {
-            println!("Missing {:?} section fields: {:?}", missing.len(), missing);
-            exit(589)
-        }
+        Chunk::report_unknown("structures", &missing, 589);
 
-        structures
+        (structures, references, extra)
     }
 
-    fn process_references(reference_tags: Vec<Tag>) {
-        let mut missing: Vec<String> = vec![];
-
-        let mut references: Vec<String> = vec![];
+    fn process_references(reference_tags: Vec<Tag>) -> Vec<StructureReference> {
+        let mut references = vec![];
 
         for reference in reference_tags {
             if TagType::End == reference.tagtype { continue }
@@ -636,81 +1628,111 @@ impl StructureProcessor for Chunk {
             let packed_coordinates = &reference.payload_long_array();
 
             for coordinates in packed_coordinates {
+                // extract the chunk z coordinate
+                let z = (coordinates >> 32 & bit_mask) as i32;
+
                 // extract the chunk x coordinate
-                let z = coordinates >> 32 & bit_mask;
+                let x = (coordinates & bit_mask) as i32;
 
-                // extract the chunk z coordinate
-                let x = coordinates & bit_mask;
+                references.push(StructureReference {
+                    structure_name: name.clone(),
+                    x,
+                    z,
+                });
             }
-
         }
 
-        if 0 < missing.len() {
-            println!("Missing {:?} reference fields: {:?}", missing.len(), missing);
-            exit(589)
-        }
+        references
     }
 
-    fn process_starts(starts_tags: Vec<Tag>) {
+    fn process_starts(starts_tags: Vec<Tag>) -> Vec<Structure> {
         let mut missing: Vec<String> = vec![];
 
-        let mut starts: Vec<String> = vec![];
-
-        let mut id = String::new();
-        let mut chunk_x = 0;
-        let mut chunk_z = 0;
+        let mut starts: Vec<Structure> = vec![];
 
         for start in starts_tags {
+            if TagType::End == start.tagtype { continue }
+
+            let mut structure = Structure {
+                structure_name: start.name.clone(),
+                x: 0,
+                z: 0,
+                bounding_box: None,
+                children: vec![],
+                extra: Tag::compound(""),
+            };
+
             for subtag in &start.subtags {
                 match subtag.name.as_str() {
                     "Children" => {
-                        // println!("Children {:?}: {:?}", subtag.name, subtag.subtags)
-                        Chunk::process_children(subtag.clone().subtags);
+                        structure.children = Chunk::process_children(subtag.clone().subtags);
                     }
                     "ChunkX" => {
-                        chunk_x = subtag.payload_int()
+                        structure.x = subtag.payload_int()
                     }
                     "ChunkZ" => {
-                        chunk_z = subtag.payload_int()
+                        structure.z = subtag.payload_int()
                     }
                     "id" => {
-                        id = subtag.payload_string()
+                        structure.structure_name = subtag.payload_string()
                     }
                     "references" => {
                         _ = subtag.payload_int() // I don't know what this is and it's always 0
                     }
                     _ => {
                         if subtag.tagtype != TagType::End {
-                            println!("{:?}: {:?}", subtag.name, subtag.subtags);
-                            missing.push(subtag.clone().name)
+                            missing.push(subtag.clone().name);
+                            structure.extra.push(subtag.clone());
                         }
                     }
                 }
             }
-        }
 
-        if 0 < missing.len() {
-            println!("Missing {:?} starts.subtag fields: {:?}", missing.len(), missing);
-            exit(589)
+            // chunks without a start still get an entry, it's just marked invalid
+            if "INVALID" == structure.structure_name { continue }
+
+            // the structure covers everything its pieces do
+            for child in &structure.children {
+                if let Some(child_box) = &child.bounding_box {
+                    structure.bounding_box = match &structure.bounding_box {
+                        Some(bounding_box) => Some(bounding_box.union(child_box)),
+                        None => Some(*child_box),
+                    };
+                }
+            }
+
+            starts.push(structure);
         }
+
+        Chunk::report_unknown("starts", &missing, 589);
+
+        starts
     }
 
-    fn process_children(children_tags: Vec<Tag>) {
+    fn process_children(children_tags: Vec<Tag>) -> Vec<StructurePiece> {
         let mut missing: Vec<String> = vec![];
 
-        let mut children: Vec<String> = vec![];
+        let mut children: Vec<StructurePiece> = vec![];
 
         for child in children_tags {
+            let mut piece = StructurePiece {
+                id: String::new(),
+                bounding_box: None,
+                template: None,
+                rotation: None,
+                extra: Tag::compound(""),
+            };
+
             for subtag in child.subtags {
                 match subtag.name.as_str() {
-                    "BB" => {}
+                    "BB" => piece.bounding_box = BoundingBox::new(subtag.payload_int_array()),
                     "BiomeType" => {}
                     "D" => {}
                     "Entrances" => {}
                     "GD" => {}
                     "hps" => {}
                     "hr" => {}
-                    "id" => {}
+                    "id" => piece.id = subtag.payload_string(),
                     "Integrity" => {}
                     "isBeached" => {}
                     "IsLarge" => {}
@@ -719,42 +1741,355 @@ impl StructureProcessor for Chunk {
                     "Num" => {}
                     "O" => {}
                     "Properties" => {} // compound
-                    "Rotation" => {} // string
-                    "Rot" => {}
+                    "Rotation" | "Rot" | "rotation" => piece.rotation = Some(subtag.payload_string()),
                     "sc" => {}
-                    "Template" => {}
+                    "Template" => piece.template = Some(subtag.payload_string()),
                     "tf" => {}
                     "TPX" => {}
                     "TPY" => {}
                     "TPZ" => {}
                     "VerticalPlacement" => {} // string
+                    // jigsaw pieces, e.g. villages and ancient cities, name their template here
+                    "pool_element" => {
+                        for element in &subtag.subtags {
+                            if "location" == element.name {
+                                piece.template = Some(element.payload_string());
+                            }
+                        }
+                    }
+                    "ground_level_delta" => {}
+                    "junctions" => {}
+                    "liquid_settings" => {}
+                    "PosX" => {}
+                    "PosY" => {}
+                    "PosZ" => {}
                     _ => {
-                        if subtag.tagtype != TagType::End && !missing.contains(&subtag.name){
-                            println!("{:?}: {:?}", subtag.name, subtag.tagtype);
-                            missing.push(subtag.clone().name)
+                        if subtag.tagtype != TagType::End {
+                            if !missing.contains(&subtag.name) {
+                                missing.push(subtag.clone().name);
+                            }
+                            piece.extra.push(subtag);
                         }
                     }
                 }
             }
-        }
 
-        if 0 < missing.len() {
-            println!("Missing {:?} starts.children fields: {:?}", missing.len(), missing);
-            exit(589)
+            children.push(piece);
         }
+
+        Chunk::report_unknown("starts.children", &missing, 589);
+
+        children
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct BlockEntity {
-    entity_type: String,
-    properties: HashMap<String, String>,
+    pub entity_type: String,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub keep_packed: bool,
+    pub data: BlockEntityData,
+    pub raw: Tag,
+}
+
+#[derive(Debug, Clone)]
+pub enum BlockEntityData {
+    Sign {
+        front_text: SignText,
+        back_text: SignText,
+        is_waxed: bool,
+    },
+    Container {
+        custom_name: Option<String>,
+        lock: Option<String>,
+        loot_table: Option<String>,
+        items: Vec<Item>,
+    },
+    Banner {
+        custom_name: Option<String>,
+        patterns: Vec<BannerPattern>,
+    },
+    Spawner {
+        entity_id: Option<String>,
+        delay: i16,
+    },
+    Beacon {
+        levels: i32,
+        primary_effect: Option<String>,
+        secondary_effect: Option<String>,
+    },
+    Lectern {
+        book: Option<Item>,
+        page: i32,
+    },
+    Unknown,
+}
+
+#[derive(Debug, Clone)]
+pub struct SignText {
+    pub messages: Vec<String>,
+    pub color: String,
+    pub has_glowing_text: bool,
+}
+
+impl SignText {
+    fn new() -> Self {
+        SignText {
+            messages: vec![],
+            color: String::from("black"),
+            has_glowing_text: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BannerPattern {
+    pub pattern: String,
+    pub color: String,
+}
+
+trait BlockEntityProcessor {
+    fn process_block_entities(tags: Vec<Tag>) -> Vec<BlockEntity>;
+    fn process_block_entity(tag: Tag) -> BlockEntity;
+    fn process_sign(tag: &Tag) -> BlockEntityData;
+    fn process_sign_text(tag: &Tag) -> SignText;
+    fn process_container(tag: &Tag) -> BlockEntityData;
+    fn process_banner(tag: &Tag) -> BlockEntityData;
+    fn process_spawner(tag: &Tag) -> BlockEntityData;
+    fn process_beacon(tag: &Tag) -> BlockEntityData;
+    fn process_lectern(tag: &Tag) -> BlockEntityData;
+}
+
+impl BlockEntityProcessor for Chunk {
+    fn process_block_entities(tags: Vec<Tag>) -> Vec<BlockEntity> {
+        let mut block_entities = vec![];
+
+        for tag in tags {
+            if TagType::Compound != tag.tagtype { continue }
+            block_entities.push(Chunk::process_block_entity(tag));
+        }
+
+        block_entities
+    }
+
+    fn process_block_entity(tag: Tag) -> BlockEntity {
+        let mut block_entity = BlockEntity {
+            entity_type: String::new(),
+            x: 0,
+            y: 0,
+            z: 0,
+            keep_packed: false,
+            data: BlockEntityData::Unknown,
+            raw: Tag::new(vec![0]),
+        };
+
+        // common fields shared by every block entity
+        for subtag in &tag.subtags {
+            match subtag.name.as_str() {
+                "id" => block_entity.entity_type = subtag.payload_string(),
+                "x" => block_entity.x = subtag.payload_int(),
+                "y" => block_entity.y = subtag.payload_int(),
+                "z" => block_entity.z = subtag.payload_int(),
+                "keepPacked" => block_entity.keep_packed = 0 != subtag.payload_byte(),
+                _ => {} // type specific, handled below
+            }
+        }
+
+        // anything with an inventory is treated as a container
+        let has_items = tag.subtags.iter().any(|subtag| "Items" == subtag.name);
+
+        block_entity.data = match block_entity.entity_type.as_str() {
+            "minecraft:sign" | "minecraft:hanging_sign" => Chunk::process_sign(&tag),
+            "minecraft:banner" => Chunk::process_banner(&tag),
+            "minecraft:mob_spawner" => Chunk::process_spawner(&tag),
+            "minecraft:beacon" => Chunk::process_beacon(&tag),
+            "minecraft:lectern" => Chunk::process_lectern(&tag),
+            "minecraft:chest" | "minecraft:trapped_chest" | "minecraft:barrel"
+            | "minecraft:shulker_box" | "minecraft:hopper" | "minecraft:dispenser"
+            | "minecraft:dropper" | "minecraft:furnace" | "minecraft:blast_furnace"
+            | "minecraft:smoker" | "minecraft:brewing_stand" => Chunk::process_container(&tag),
+            _ => match has_items {
+                true => Chunk::process_container(&tag),
+                false => BlockEntityData::Unknown,
+            }
+        };
+
+        block_entity.raw = tag;
+
+        block_entity
+    }
+
+    fn process_sign(tag: &Tag) -> BlockEntityData {
+        let mut front_text = SignText::new();
+        let mut back_text = SignText::new();
+        let mut is_waxed = false;
+
+        for subtag in &tag.subtags {
+            match subtag.name.as_str() {
+                // 1.20 and newer
+                "front_text" => front_text = Chunk::process_sign_text(subtag),
+                "back_text" => back_text = Chunk::process_sign_text(subtag),
+                "is_waxed" => is_waxed = 0 != subtag.payload_byte(),
+                // older signs only have a front, written as four separate lines
+                "Text1" | "Text2" | "Text3" | "Text4" => {
+                    front_text.messages.push(subtag.payload_string())
+                }
+                "Color" => front_text.color = subtag.payload_string(),
+                "GlowingText" => front_text.has_glowing_text = 0 != subtag.payload_byte(),
+                _ => {}
+            }
+        }
+
+        BlockEntityData::Sign { front_text, back_text, is_waxed }
+    }
+
+    fn process_sign_text(tag: &Tag) -> SignText {
+        let mut sign_text = SignText::new();
+
+        for subtag in &tag.subtags {
+            match subtag.name.as_str() {
+                "messages" => {
+                    for message in &subtag.subtags {
+                        sign_text.messages.push(message.payload_string());
+                    }
+                }
+                "color" => sign_text.color = subtag.payload_string(),
+                "has_glowing_text" => sign_text.has_glowing_text = 0 != subtag.payload_byte(),
+                _ => {}
+            }
+        }
+
+        sign_text
+    }
+
+    fn process_container(tag: &Tag) -> BlockEntityData {
+        let mut custom_name = None;
+        let mut lock = None;
+        let mut loot_table = None;
+        let mut items = vec![];
+
+        for subtag in &tag.subtags {
+            match subtag.name.as_str() {
+                "CustomName" => custom_name = subtag.payload_text(),
+                // a plain string until 1.21.2 made it an item predicate, which stays in raw
+                "Lock" if TagType::String == subtag.tagtype => lock = Some(subtag.payload_string()),
+                "LootTable" => loot_table = Some(subtag.payload_string()),
+                "Items" => items = Item::from_list(&subtag.subtags),
+                _ => {}
+            }
+        }
+
+        BlockEntityData::Container { custom_name, lock, loot_table, items }
+    }
+
+    fn process_banner(tag: &Tag) -> BlockEntityData {
+        let mut custom_name = None;
+        let mut patterns = vec![];
+
+        for subtag in &tag.subtags {
+            match subtag.name.as_str() {
+                "CustomName" => custom_name = subtag.payload_text(),
+                // "Patterns" before 1.20.5 used short codes and dye ids, "patterns" uses names
+                "Patterns" | "patterns" => {
+                    for compound in &subtag.subtags {
+                        let mut banner_pattern = BannerPattern {
+                            pattern: String::new(),
+                            color: String::new(),
+                        };
+
+                        for field in &compound.subtags {
+                            match field.name.as_str() {
+                                "Pattern" | "pattern" => banner_pattern.pattern = field.payload_string(),
+                                "Color" => banner_pattern.color = field.payload_int().to_string(),
+                                "color" => banner_pattern.color = field.payload_string(),
+                                _ => {}
+                            }
+                        }
+
+                        patterns.push(banner_pattern);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        BlockEntityData::Banner { custom_name, patterns }
+    }
+
+    fn process_spawner(tag: &Tag) -> BlockEntityData {
+        let mut entity_id = None;
+        let mut delay = 0;
+
+        for subtag in &tag.subtags {
+            match subtag.name.as_str() {
+                "Delay" => delay = subtag.payload_short(),
+                "SpawnData" => {
+                    for field in &subtag.subtags {
+                        match field.name.as_str() {
+                            // 1.18 and newer nest the entity
+                            "entity" => {
+                                for entity_field in &field.subtags {
+                                    if "id" == entity_field.name {
+                                        entity_id = Some(entity_field.payload_string());
+                                    }
+                                }
+                            }
+                            "id" => entity_id = Some(field.payload_string()),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        BlockEntityData::Spawner { entity_id, delay }
+    }
+
+    fn process_beacon(tag: &Tag) -> BlockEntityData {
+        let mut levels = 0;
+        let mut primary_effect = None;
+        let mut secondary_effect = None;
+
+        for subtag in &tag.subtags {
+            match subtag.name.as_str() {
+                "Levels" => levels = subtag.payload_int(),
+                // effect names since 1.20.2, numeric effect ids before that
+                "primary_effect" => primary_effect = Some(subtag.payload_string()),
+                "secondary_effect" => secondary_effect = Some(subtag.payload_string()),
+                "Primary" => primary_effect = Some(subtag.payload_int().to_string()),
+                "Secondary" => secondary_effect = Some(subtag.payload_int().to_string()),
+                _ => {}
+            }
+        }
+
+        BlockEntityData::Beacon { levels, primary_effect, secondary_effect }
+    }
+
+    fn process_lectern(tag: &Tag) -> BlockEntityData {
+        let mut book = None;
+        let mut page = 0;
+
+        for subtag in &tag.subtags {
+            match subtag.name.as_str() {
+                "Book" => book = Some(Item::new(subtag)),
+                "Page" => page = subtag.payload_int(),
+                _ => {}
+            }
+        }
+
+        BlockEntityData::Lectern { book, page }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct CarvingMask {
-    air: Vec<u8>,
-    liquid: Vec<u8>,
+    /// Bit sets of carved blocks, one bit per block from the bottom of the chunk up
+    pub air: Vec<u64>,
+    pub liquid: Vec<u64>,
+    pub min_y: i32,
 }
 
 impl CarvingMask {
@@ -762,7 +2097,130 @@ impl CarvingMask {
         CarvingMask {
             air: vec![],
             liquid: vec![],
+            min_y: 0,
+        }
+    }
+
+    /// Whether a carver has dug out the block, x and z may be absolute or within the chunk
+    pub fn is_carved(&self, x: i32, y: i32, z: i32) -> bool {
+        if y < self.min_y { return false }
+
+        let index = ((x & 15) | (z & 15) << 4 | (y - self.min_y) << 8) as usize;
+        let bit = |bits: &Vec<u64>| bits.get(index / 64).is_some_and(|long| 0 != long >> (index % 64) & 1);
+
+        bit(&self.air) || bit(&self.liquid)
+    }
+
+    pub fn carved_count(&self) -> usize {
+        self.air.iter().chain(self.liquid.iter()).map(|long| long.count_ones() as usize).sum()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BlendingData {
+    pub min_section: i32,
+    pub max_section: i32,
+    /// Old terrain heights used to blend the surface, one per edge column
+    pub heights: Vec<f64>,
+    /// Only written by 1.18, whether the old chunk's noise should be blended as well
+    pub old_noise: bool,
+}
+
+/// A block whose shape still needs updating once generation finishes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostProcessing {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+trait ProtoChunkProcessor {
+    fn process_carving_masks(tags: Vec<Tag>) -> CarvingMask;
+    fn process_blending_data(tags: Vec<Tag>) -> BlendingData;
+    fn process_post_processing(tags: Vec<Tag>, x_pos: i32, y_pos: i32, z_pos: i32) -> Vec<PostProcessing>;
+}
+
+impl ProtoChunkProcessor for Chunk {
+    fn process_carving_masks(tags: Vec<Tag>) -> CarvingMask {
+        let mut carving_mask = CarvingMask::new();
+        let mut missing: Vec<String> = vec![];
+
+        for tag in tags {
+            // 1.18 moved from java's byte array bit sets to long arrays, the bit order is the same
+            let bits: Vec<u64> = match tag.tagtype {
+                TagType::LongArray => tag.payload_long_array().iter().map(|long| *long as u64).collect(),
+                TagType::ByteArray => tag.payload_byte_array()
+                    .chunks(8)
+                    .map(|bytes| bytes.iter().rev().fold(0u64, |long, byte| long << 8 | *byte as u64))
+                    .collect(),
+                _ => vec![],
+            };
+
+            match tag.name.as_str() {
+                "AIR" => carving_mask.air = bits,
+                "LIQUID" => carving_mask.liquid = bits,
+                _ => {
+                    if TagType::End != tag.tagtype {
+                        missing.push(tag.name.clone());
+                    }
+                }
+            }
+        }
+
+        Chunk::report_unknown("carving mask", &missing, 42069);
+
+        carving_mask
+    }
+
+    fn process_blending_data(tags: Vec<Tag>) -> BlendingData {
+        let mut blending_data = BlendingData {
+            min_section: 0,
+            max_section: 0,
+            heights: vec![],
+            old_noise: false,
+        };
+        let mut missing: Vec<String> = vec![];
+
+        for tag in tags {
+            match tag.name.as_str() {
+                "min_section" => blending_data.min_section = tag.payload_int(),
+                "max_section" => blending_data.max_section = tag.payload_int(),
+                "heights" => {
+                    blending_data.heights = tag.subtags.iter()
+                        .map(|height| height.payload_double())
+                        .collect();
+                }
+                "old_noise" => blending_data.old_noise = 0 != tag.payload_byte(),
+                _ => {
+                    if TagType::End != tag.tagtype {
+                        missing.push(tag.name.clone());
+                    }
+                }
+            }
+        }
+
+        Chunk::report_unknown("blending_data", &missing, 42069);
+
+        blending_data
+    }
+
+    fn process_post_processing(tags: Vec<Tag>, x_pos: i32, y_pos: i32, z_pos: i32) -> Vec<PostProcessing> {
+        let mut post_processing = vec![];
+
+        for (section_index, section) in tags.iter().enumerate() {
+            let section_y = y_pos + section_index as i32;
+
+            for packed in &section.subtags {
+                let packed = packed.payload_short() as i32;
+                post_processing.push(PostProcessing {
+                    x: x_pos * 16 + (packed & 15),
+                    y: section_y * 16 + (packed >> 4 & 15),
+                    z: z_pos * 16 + (packed >> 8 & 15),
+                });
+            }
         }
+
+        post_processing
     }
 }
 
@@ -791,10 +2249,49 @@ impl Heightmap {
 
 #[derive(Debug, Clone)]
 pub struct TileTick {
-    i: Box<str>,
-    p: Vec<Box<str>>,
-    t: i32,
-    x: i32,
-    y: i32,
-    z: i32,
+    pub i: Box<str>,
+    pub p: i32,
+    pub t: i32,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+trait TileTickProcessor {
+    fn process_tile_ticks(tags: Vec<Tag>) -> Vec<TileTick>;
+}
+
+impl TileTickProcessor for Chunk {
+    fn process_tile_ticks(tags: Vec<Tag>) -> Vec<TileTick> {
+        let mut tile_ticks = vec![];
+
+        for compound in tags {
+            if TagType::Compound != compound.tagtype { continue }
+
+            let mut tile_tick = TileTick {
+                i: Box::from(""),
+                p: 0,
+                t: 0,
+                x: 0,
+                y: 0,
+                z: 0,
+            };
+
+            for tag in compound.subtags {
+                match tag.name.as_str() {
+                    "i" => tile_tick.i = tag.payload_string().into_boxed_str(),
+                    "p" => tile_tick.p = tag.payload_int(),
+                    "t" => tile_tick.t = tag.payload_int(),
+                    "x" => tile_tick.x = tag.payload_int(),
+                    "y" => tile_tick.y = tag.payload_int(),
+                    "z" => tile_tick.z = tag.payload_int(),
+                    _ => {}
+                }
+            }
+
+            tile_ticks.push(tile_tick);
+        }
+
+        tile_ticks
+    }
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 use std::{env, fs};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 use std::process::exit;
 
@@ -10,8 +10,17 @@ use serde_derive::{Deserialize, Serialize};
 use sha1::{Digest, Sha1};
 use toml::Value;
 
+use crate::chunk::Chunk;
+use crate::dimension::DimensionType;
+use crate::launcher::JarLocator;
+use crate::layout::WorldLayout;
+use crate::level::Level;
+use crate::nbt::NBT;
 use crate::world::{DeepDirectoryDriver, Hasher, World};
 
+/// Layers of bedrock making up a ceiling, y 123 to 127 in the nether
+const BEDROCK_ROOF: i32 = 5;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
     pub minecraft_jar: String,
@@ -19,6 +28,11 @@ pub struct Config {
     pub cache_dir: PathBuf,
     pub ignore_cache: bool,
     pub force_render: bool,
+    #[serde(default)]
+    pub strict_parsing: bool,
+    /// Worker threads for decoding chunks, 0 uses every core
+    #[serde(default)]
+    pub threads: usize,
     pub worlds: HashMap<String, String>,
     pub textures: HashMap<String, String>,
     pub renders: Vec<Render>,
@@ -30,7 +44,49 @@ pub struct Render {
     pub title: String,
     pub mode: String,
     pub dimension: String,
-    pub textures: String,
+    /// Resource packs from the top of the stack down, the vanilla jar is always at the bottom
+    pub textures: Vec<String>,
+    #[serde(default)]
+    pub proto_chunks: ProtoChunks,
+    /// Filled in from the world when the render is validated
+    #[serde(skip)]
+    pub dimension_type: Option<DimensionType>,
+    /// The version the world was last played in, filled in when the render is validated
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+/// How a render treats chunks that haven't finished generating
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProtoChunks {
+    Render,
+    Dim,
+    #[default]
+    Hide,
+}
+
+impl Render {
+    /// Whether the chunk should be drawn at all
+    pub fn includes_chunk(&self, chunk: &Chunk) -> bool {
+        !chunk.status.is_proto() || ProtoChunks::Hide != self.proto_chunks
+    }
+
+    /// Whether the chunk should be drawn darker than its finished neighbours
+    pub fn dims_chunk(&self, chunk: &Chunk) -> bool {
+        chunk.status.is_proto() && ProtoChunks::Dim == self.proto_chunks
+    }
+
+    /// The lowest and highest block y to render, dimensions with a ceiling start below the bedrock
+    /// roof at the top of their logical height so it doesn't hide everything
+    pub fn y_bounds(&self) -> (i32, i32) {
+        let dimension_type = self.dimension_type.clone().unwrap_or(DimensionType::overworld());
+
+        match dimension_type.has_ceiling {
+            true => (dimension_type.min_y, dimension_type.min_y + dimension_type.logical_height - 1 - BEDROCK_ROOF),
+            false => (dimension_type.min_y, dimension_type.min_y + dimension_type.height - 1),
+        }
+    }
 }
 
 impl Config {
@@ -44,6 +100,8 @@ impl Config {
             cache_dir: default_cache.clone(),
             ignore_cache: false,
             force_render: false,
+            strict_parsing: false,
+            threads: 0,
             worlds: Default::default(),
             textures: Default::default(),
             renders: vec![],
@@ -69,6 +127,8 @@ impl Config {
                     "cache" => config.cache_dir = Config::validate_directory(val),
                     "ignore_cache" => config.ignore_cache = val.as_bool().unwrap(),
                     "force_render" => config.force_render = val.as_bool().unwrap(),
+                    "strict_parsing" => config.strict_parsing = val.as_bool().unwrap(),
+                    "threads" => config.threads = Config::validate_threads(val),
 
                     // list of variables
                     "worlds" => config.worlds = Config::parse_worlds(val),
@@ -81,16 +141,24 @@ impl Config {
             }
         }
 
-        // find default minecraft jar if none was provided
+        // every world is opened once here, backups take a while to index
+        let worlds = config.open_worlds(&render_list);
+
+        // find the minecraft jar matching the rendered worlds if none was provided
         if 0 == config.minecraft_jar.len() {
-            config.minecraft_jar = Config::validate_minecraft_jar(World::default_jar_path());
+            config.minecraft_jar = Config::validate_minecraft_jar(config.locate_minecraft_jar(&worlds));
+        }
+
+        // textures fall back to the minecraft jar's own
+        if !config.textures.contains_key("default") {
+            config.textures.insert(String::from("default"), config.minecraft_jar.clone());
         }
 
         // extract necessary minecraft jar data
         config.extract_minecraft_jar();
 
         // validate render list
-        config.renders = Config::validate_renders(render_list, &config);
+        config.renders = Config::validate_renders(render_list, &config, &worlds);
 
         // save validated config to cache
         config.save_config()
@@ -219,33 +287,88 @@ impl Config {
         }
     }
 
+    /// Open every world a render uses, or every configured world when there are no renders, along
+    /// with its level.dat, by world path
+    fn open_worlds(&self, render_list: &Vec<Render>) -> BTreeMap<String, (WorldLayout, Level)> {
+        let mut world_paths: Vec<&String> = render_list.iter()
+            .map(|render| self.worlds.get(&render.world).unwrap_or(&render.world))
+            .collect();
+        if world_paths.is_empty() {
+            world_paths = self.worlds.values().collect();
+        }
+
+        let mut worlds = BTreeMap::new();
+        for world_path in world_paths {
+            if worlds.contains_key(world_path) { continue }
+
+            match WorldLayout::detect(world_path) {
+                Ok(layout) => {
+                    let level = match layout.source.read("level.dat") {
+                        Ok(bytes) => Level::from_nbt(NBT::from_bytes(bytes)),
+                        Err(_) => Level::new(),
+                    };
+                    worlds.insert(world_path.clone(), (layout, level));
+                }
+                Err(err) => eprintln!("Invalid world path ({:?}): {err}", world_path),
+            }
+        }
+
+        worlds
+    }
+
+    /// The installed jar closest to the newest version any rendered world was last played in
+    fn locate_minecraft_jar(&self, worlds: &BTreeMap<String, (WorldLayout, Level)>) -> String {
+        let world_paths: Vec<PathBuf> = worlds.keys().map(PathBuf::from).collect();
+        let target = worlds.values().filter_map(|(_, level)| level.version()).max();
+
+        let locator = JarLocator::discover(&world_paths);
+        let candidate = match locator.select(target.as_ref(), &world_paths) {
+            Some(candidate) => candidate,
+            None => return World::default_jar_path(),
+        };
+        println!("using {} jar for {} from {:?}", candidate.source, candidate.version, &candidate.path);
+
+        match candidate.resolve(&self.cache_dir) {
+            Ok(path) => String::from(path.to_string_lossy()),
+            Err(err) => {
+                eprintln!("Error extracting bundled server jar: {err}");
+                World::default_jar_path()
+            }
+        }
+    }
+
     fn validate_minecraft_jar(input: String) -> String {
         let input = input.as_str().trim_matches('"');
         let target_jar = PathBuf::from(input);
 
-        // check if it exists
-        if !target_jar.exists() {
-            if input == &World::default_jar_path() {
+        // check if it exists and make sure it's not a directory
+        if !target_jar.exists() || target_jar.is_dir() {
+            let default_jar = World::default_jar_path();
+            if input == default_jar {
                 panic!("Default minecraft jar couldn't be found.");
             }
-            eprintln!("Minecraft jar could not be found: {:?}", &input);
-            println!("Attempting to use default jar");
-            return Config::validate_minecraft_jar(World::default_jar_path());
-        }
-
-        // make sure it's not a directory
-        if target_jar.is_dir() {
-            if input == &World::default_jar_path() {
-                panic!("Default minecraft jar couldn't be found.");
+            match target_jar.exists() {
+                true => eprintln!("Minecraft jar is a directory: {:?}", &input),
+                false => eprintln!("Minecraft jar could not be found: {:?}", &input),
             }
-            eprintln!("Minecraft jar is a directory: {:?}", &input);
             println!("Attempting to use default jar");
-            return Config::validate_minecraft_jar(World::default_jar_path());
+            return Config::validate_minecraft_jar(default_jar);
         }
 
         input.to_string()
     }
 
+    /// The same rules as `--threads`, a whole number where 0 uses every core
+    fn validate_threads(input: &Value) -> usize {
+        match input.as_integer().and_then(|threads| usize::try_from(threads).ok()) {
+            Some(threads) => threads,
+            None => {
+                eprintln!("Error: invalid thread count {}, use a whole number, 0 uses every core", input);
+                exit(45)
+            }
+        }
+    }
+
     fn validate_directory(input: &Value) -> PathBuf {
         // convert path into string
         let binding = input.to_string();
@@ -267,7 +390,7 @@ impl Config {
         target_dir
     }
 
-    fn validate_renders(render_list: Vec<Render>, config: &Config) -> Vec<Render> {
+    fn validate_renders(render_list: Vec<Render>, config: &Config, worlds: &BTreeMap<String, (WorldLayout, Level)>) -> Vec<Render> {
         let mut validated_list: Vec<Render> = vec![];
 
         for mut render_conf in render_list {
@@ -275,50 +398,53 @@ impl Config {
             let mut valid_dimension = false;
             let mut valid_textures = false;
 
-            // validate world path
-            let world_path = Path::new(&render_conf.world);
+            // validate world path, either a world directory or a backup of one
             if config.worlds.contains_key(&render_conf.world) {
                 // has a valid world key
                 render_conf.world = config.worlds[&render_conf.world].clone();
+            }
+            let world = worlds.get(&render_conf.world);
+            if let Some((_, level)) = world {
                 valid_world = true;
-            } else if world_path.exists() && world_path.is_dir() {
-                // has a valid world path
-                valid_world = true;
-            } else {
-                eprintln!("Invalid world path: {:?}", &world_path);
+                render_conf.version = level.version().map(|version| version.id);
             }
 
-            // validate the dimension
-            let mut dimension_path = PathBuf::from(&render_conf.world);
-            let dimension = match render_conf.dimension.to_lowercase().as_str() {
-                "overworld" => { "" }
-                "nether" => { "DIM1" }
-                "the end" => { "DIM-1" }
-                &_ => { &render_conf.dimension }
-            };
-            let mut subdirs = vec![dimension, "region"];
-            subdirs.retain(|&x| 0 < x.len());
-            for dir in subdirs { dimension_path.push(dir) }
-            println!("dimension_path: {:?}", &dimension_path);
-            if dimension_path.exists() && dimension_path.is_dir() {
-                let binding = dimension_path.as_os_str().to_str().unwrap();
-                render_conf.dimension = String::from(dimension_path.as_os_str().to_str().unwrap());
-                valid_dimension = true;
+            // validate the dimension, vanilla or from a data pack, wherever the server keeps it
+            if let Some((layout, level)) = world {
+                if let Some(dimension) = layout.find(&render_conf.dimension, level.data_version) {
+                    let region_path = match dimension.source.local_path(&dimension.region_directory()) {
+                        Some(region_path) => region_path,
+                        None => Path::new(&render_conf.world).join(dimension.region_directory()),
+                    };
+                    render_conf.dimension = String::from(region_path.to_string_lossy());
+                    render_conf.dimension_type = Some(dimension.dimension_type);
+                    valid_dimension = true;
+                }
             }
             println!("render_conf.dimension [{:?}]: {:?}", &valid_dimension, &render_conf.dimension);
 
-            // validate textures path
-            let textures_path = Path::new(&render_conf.textures);
-            if config.textures.contains_key(&render_conf.textures) {
-                render_conf.textures = config.textures[&render_conf.textures].clone();
-                valid_textures = true;
-            } else if textures_path.exists() && !textures_path.is_dir() {
-                valid_textures = true;
-            } else {
-                println!("couldn't find textures '{:?}', using default", &render_conf.textures);
-                render_conf.textures = config.textures["default"].clone();
-                valid_textures = true;
+            // validate each pack in the stack, dropping the ones that can't be found
+            let mut packs = vec![];
+            for textures in &render_conf.textures {
+                let textures_path = Path::new(textures);
+                if config.textures.contains_key(textures) {
+                    packs.push(config.textures[textures].clone());
+                } else if textures_path.is_file() || textures_path.join("assets").is_dir() {
+                    packs.push(textures.clone());
+                } else {
+                    println!("couldn't find textures '{:?}', leaving it out of the stack", textures);
+                }
+            }
+            if packs.is_empty() {
+                packs.push(config.textures["default"].clone());
+            }
+
+            // anything no pack has comes from the vanilla jar
+            if !packs.contains(&config.minecraft_jar) {
+                packs.push(config.minecraft_jar.clone());
             }
+            render_conf.textures = packs;
+            valid_textures = true;
 
             // it's valid!
             let valid_checks = vec![valid_world, valid_dimension, valid_textures];
@@ -402,11 +528,6 @@ impl Config {
             None => {} // do nothing
         }
 
-        // add default texture path if missing
-        if !output.contains_key("default") {
-            output.insert(String::from("default"), World::default_jar_path());
-        }
-
         output
     }
 
@@ -431,7 +552,10 @@ impl Config {
             title: "My Render".to_string(),
             mode: "default".to_string(),
             dimension: "overworld".to_string(),
-            textures: "default".to_string(),
+            textures: vec!["default".to_string()],
+            proto_chunks: ProtoChunks::Hide,
+            dimension_type: None,
+            version: None,
         };
 
         match input.as_table() {
@@ -442,7 +566,24 @@ impl Config {
                         "title" => render.title = String::from(value.to_string().as_str().trim_matches('"')),
                         "mode" => render.mode = String::from(value.to_string().as_str().trim_matches('"')),
                         "dimension" => render.dimension = String::from(value.to_string().as_str().trim_matches('"')),
-                        "textures" => render.textures = String::from(value.to_string().as_str().trim_matches('"')),
+                        "textures" => {
+                            // a single pack or a stack of them
+                            render.textures = match value.as_array() {
+                                Some(packs) => packs.iter().filter_map(|pack| pack.as_str()).map(String::from).collect(),
+                                None => vec![String::from(value.to_string().as_str().trim_matches('"'))],
+                            }
+                        }
+                        "proto_chunks" => {
+                            render.proto_chunks = match value.as_str().unwrap_or("").to_lowercase().as_str() {
+                                "render" => ProtoChunks::Render,
+                                "dim" => ProtoChunks::Dim,
+                                "hide" => ProtoChunks::Hide,
+                                _ => {
+                                    println!("unknown proto_chunks setting {:?}, hiding proto-chunks", &value);
+                                    ProtoChunks::Hide
+                                }
+                            }
+                        }
                         _ => {} // ignore unknown keys
                     }
                 }
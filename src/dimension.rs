@@ -0,0 +1,337 @@
+//! DIMENSIONS
+//!
+//! Every dimension keeps its own `region`, `entities` and `poi` directories. The overworld uses the
+//! world directory itself, the nether and the end use `DIM-1` and `DIM1`, and dimensions added by
+//! data packs live under `dimensions/<namespace>/<name>`.
+//!
+//! | DIMENSION -->   | minecraft:overworld | minecraft:the_nether | minecraft:the_end | <namespace>:<name>          |
+//! |-----------------|---------------------|----------------------|-------------------|-----------------------------|
+//! | DIRECTORY -->   | <world>             | <world>/DIM-1        | <world>/DIM1      | <world>/dimensions/<ns>/<n> |
+//!
+//! Servers don't always keep every dimension in the world directory, see layout.rs.
+
+//! DIMENSION TYPES
+//!
+//! How tall a dimension is and whether it has a sky comes from its dimension type. Data packs
+//! define dimensions in `data/<namespace>/dimension/<name>.json`, naming a type that is either
+//! inlined or defined in `data/<namespace>/dimension_type/<name>.json`. Data packs sit in the
+//! world's `datapacks` directory, either as directories or zip files, and only those level.dat
+//! lists as enabled are used, in its order, so later packs override earlier ones.
+//!
+//! ```json
+//! {"min_y": 0, "height": 256, "logical_height": 128, "has_ceiling": true, "has_skylight": false, ...}
+//! ```
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::sync::Arc;
+
+use log::warn;
+use serde_json::Value;
+use zip::read::ZipArchive;
+
+use crate::level::Level;
+use crate::source::{join, WorldSource};
+use crate::version::MinecraftVersion;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DimensionType {
+    pub min_y: i32,
+    pub height: i32,
+    /// How far up from min_y the game actually plays, the nether's roof sits at its top
+    pub logical_height: i32,
+    pub has_ceiling: bool,
+    pub has_skylight: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Dimension {
+    pub id: String,
+    /// Relative to the world directory, empty for the overworld
+    pub directory: String,
+    /// The world folder the dimension is in, which isn't always the world's own
+    pub source: Arc<WorldSource>,
+    pub dimension_type: DimensionType,
+}
+
+impl DimensionType {
+    /// The overworld as it's been since 1.18
+    pub fn overworld() -> Self {
+        DimensionType { min_y: -64, height: 384, logical_height: 384, has_ceiling: false, has_skylight: true }
+    }
+
+    /// The built in dimension types, worlds from before the 1.18 snapshots are 256 blocks tall
+    /// everywhere
+    pub fn vanilla(type_id: &str, data_version: i32) -> Option<Self> {
+        let tall = match MinecraftVersion::from_data_version(data_version) {
+            Some(version) => MinecraftVersion::parse("1.18").unwrap() <= version,
+            // unknown, or newer than every release we know of
+            None => true,
+        };
+
+        let dimension_type = match type_id {
+            "minecraft:overworld" | "minecraft:overworld_caves" => DimensionType {
+                min_y: if tall { -64 } else { 0 },
+                height: if tall { 384 } else { 256 },
+                logical_height: if tall { 384 } else { 256 },
+                has_ceiling: "minecraft:overworld_caves" == type_id,
+                has_skylight: true,
+            },
+            "minecraft:the_nether" => DimensionType {
+                min_y: 0,
+                height: 256,
+                logical_height: 128,
+                has_ceiling: true,
+                has_skylight: false,
+            },
+            "minecraft:the_end" => DimensionType {
+                min_y: 0,
+                height: 256,
+                logical_height: 256,
+                has_ceiling: false,
+                has_skylight: false,
+            },
+            _ => return None,
+        };
+
+        Some(dimension_type)
+    }
+
+    fn from_json(json: &Value) -> Option<Self> {
+        let height = json.get("height")?.as_i64()? as i32;
+
+        Some(DimensionType {
+            min_y: json.get("min_y")?.as_i64()? as i32,
+            height,
+            logical_height: json.get("logical_height").and_then(|value| value.as_i64()).map_or(height, |value| value as i32),
+            has_ceiling: json.get("has_ceiling").and_then(|value| value.as_bool()).unwrap_or(false),
+            has_skylight: json.get("has_skylight").and_then(|value| value.as_bool()).unwrap_or(true),
+        })
+    }
+
+    /// The lowest and highest section y index inside the dimension
+    pub fn section_range(&self) -> (i32, i32) {
+        (self.min_y >> 4, (self.min_y + self.height - 1) >> 4)
+    }
+
+    pub fn contains_y(&self, y: i32) -> bool {
+        self.min_y <= y && y < self.min_y + self.height
+    }
+}
+
+impl Dimension {
+    /// The directory within the world holding a dimension's region, entities and poi folders. The
+    /// nether and the end live in DIM-1 and DIM1, namespaced ids live under `dimensions`, and
+    /// anything else is taken as a path within the world directory.
+    pub fn directory(dimension: &str) -> String {
+        match dimension.to_lowercase().as_str() {
+            "overworld" | "minecraft:overworld" => String::new(),
+            "nether" | "the_nether" | "minecraft:the_nether" => String::from("DIM-1"),
+            "end" | "the end" | "the_end" | "minecraft:the_end" => String::from("DIM1"),
+            id => match id.split_once(':') {
+                Some((namespace, name)) => format!("dimensions/{}/{}", namespace, name),
+                None => String::from(dimension.trim_matches('/')),
+            },
+        }
+    }
+
+    /// Find every dimension with region files in the world, along with its dimension type from
+    /// `types`, see `DataPackDimensions::load`
+    pub fn discover(source: &Arc<WorldSource>, types: &HashMap<String, DimensionType>, data_version: i32) -> Vec<Dimension> {
+        Dimension::ids(source).into_iter()
+            .map(|id| Dimension::resolve(source, id, types, data_version))
+            .collect()
+    }
+
+    /// Look up a single dimension by the name used in the config, e.g. "nether" or "mypack:mining"
+    pub fn find(source: &Arc<WorldSource>, dimension: &str, types: &HashMap<String, DimensionType>, data_version: i32) -> Option<Dimension> {
+        let directory = Dimension::directory(dimension);
+        let id = Dimension::ids(source).into_iter()
+            .find(|id| Dimension::directory(id) == directory)?;
+
+        Some(Dimension::resolve(source, id, types, data_version))
+    }
+
+    /// The ids of the dimensions that have region files
+    fn ids(source: &WorldSource) -> Vec<String> {
+        let mut ids = vec![
+            String::from("minecraft:overworld"),
+            String::from("minecraft:the_nether"),
+            String::from("minecraft:the_end"),
+        ];
+
+        // data pack dimensions are found by their directories, whether or not the pack is still around
+        for namespace in Dimension::subdirectories(source, "dimensions") {
+            for name in Dimension::subdirectories(source, &join("dimensions", &namespace)) {
+                let id = format!("{}:{}", namespace, name);
+                if !ids.contains(&id) { ids.push(id) }
+            }
+        }
+
+        // forge worlds from before 1.16 number their modded dimensions, e.g. DIM7
+        for folder in source.list("") {
+            let numbered = match folder.strip_prefix("DIM") {
+                Some(number) => number.parse::<i32>().is_ok(),
+                None => false,
+            };
+            if numbered && !["DIM-1", "DIM1"].contains(&folder.as_str()) { ids.push(folder) }
+        }
+
+        ids.retain(|id| source.is_dir(&join(&Dimension::directory(id), "region")));
+
+        ids
+    }
+
+    fn resolve(source: &Arc<WorldSource>, id: String, types: &HashMap<String, DimensionType>, data_version: i32) -> Dimension {
+        let dimension_type = match types.get(&id) {
+            Some(dimension_type) => dimension_type.clone(),
+            None => match DimensionType::vanilla(&id, data_version) {
+                Some(dimension_type) => dimension_type,
+                None => {
+                    warn!("No dimension type found for {:?}, assuming overworld bounds", &id);
+                    DimensionType::vanilla("minecraft:overworld", data_version).unwrap()
+                }
+            },
+        };
+
+        Dimension { directory: Dimension::directory(&id), id, dimension_type, source: source.clone() }
+    }
+
+    /// The region directory within the world
+    pub fn region_directory(&self) -> String {
+        join(&self.directory, "region")
+    }
+
+    fn subdirectories(source: &WorldSource, directory: &str) -> Vec<String> {
+        source.list(directory).into_iter()
+            .filter(|name| source.is_dir(&join(directory, name)))
+            .collect()
+    }
+}
+
+/// Dimension types worked out from the dimension definitions in a world's data packs
+pub struct DataPackDimensions {
+    dimensions: HashMap<String, Value>,
+    dimension_types: HashMap<String, Value>,
+}
+
+impl DataPackDimensions {
+    /// The dimension types of every dimension the world's data packs define, by dimension id
+    pub fn load(source: &WorldSource, data_version: i32) -> HashMap<String, DimensionType> {
+        let mut data_packs = DataPackDimensions {
+            dimensions: HashMap::new(),
+            dimension_types: HashMap::new(),
+        };
+
+        // only the packs level.dat has enabled count, and later ones override earlier ones
+        let mut packs = source.list("datapacks");
+        if let Some(enabled) = Level::enabled_data_packs_of(source) {
+            packs = enabled.iter()
+                .filter_map(|pack| pack.strip_prefix("file/"))
+                .filter(|pack| packs.iter().any(|found| found == pack))
+                .map(String::from)
+                .collect();
+        }
+
+        for pack in packs {
+            let pack_path = join("datapacks", &pack);
+            match source.is_dir(&pack_path) {
+                true => data_packs.read_directory(source, &join(&pack_path, "data")),
+                false => data_packs.read_zip(source, &pack_path),
+            }
+        }
+
+        let mut types = HashMap::new();
+        for (id, dimension) in &data_packs.dimensions {
+            let dimension_type = match dimension.get("type") {
+                // inlined dimension type
+                Some(Value::Object(_)) => DimensionType::from_json(&dimension["type"]),
+                Some(Value::String(type_id)) => match data_packs.dimension_types.get(type_id) {
+                    Some(json) => DimensionType::from_json(json),
+                    None => DimensionType::vanilla(type_id, data_version),
+                },
+                _ => None,
+            };
+
+            match dimension_type {
+                Some(dimension_type) => { types.insert(id.clone(), dimension_type); }
+                None => warn!("Couldn't work out the dimension type of {:?}", id),
+            }
+        }
+
+        types
+    }
+
+    /// Read `<namespace>/dimension/*.json` and `<namespace>/dimension_type/*.json` from a data directory
+    fn read_directory(&mut self, source: &WorldSource, data_path: &str) {
+        for namespace in Dimension::subdirectories(source, data_path) {
+            for (folder, is_type) in [("dimension", false), ("dimension_type", true)] {
+                let folder_path = join(&join(data_path, &namespace), folder);
+
+                for file_name in source.list(&folder_path) {
+                    let name = match file_name.strip_suffix(".json") {
+                        Some(name) => name,
+                        None => continue,
+                    };
+
+                    if let Ok(bytes) = source.read(&join(&folder_path, &file_name)) {
+                        self.insert(&namespace, name, is_type, &String::from_utf8_lossy(&bytes));
+                    }
+                }
+            }
+        }
+    }
+
+    fn read_zip(&mut self, source: &WorldSource, zip_path: &str) {
+        let bytes = match source.read(zip_path) {
+            Ok(bytes) => bytes,
+            Err(_) => return,
+        };
+        let mut archive = match ZipArchive::new(Cursor::new(bytes)) {
+            Ok(archive) => archive,
+            Err(err) => {
+                warn!("Skipping data pack {:?}: {err}", zip_path);
+                return;
+            }
+        };
+
+        for i in 0..archive.len() {
+            let mut zipfile = match archive.by_index(i) {
+                Ok(zipfile) => zipfile,
+                Err(_) => continue,
+            };
+
+            // data/<namespace>/<folder>/<name>.json
+            let filename = String::from(zipfile.name());
+            let parts: Vec<&str> = filename.split('/').collect();
+            if 4 != parts.len() || "data" != parts[0] || !parts[3].ends_with(".json") { continue }
+
+            let is_type = match parts[2] {
+                "dimension" => false,
+                "dimension_type" => true,
+                _ => continue,
+            };
+
+            let mut content = String::new();
+            if zipfile.read_to_string(&mut content).is_ok() {
+                self.insert(parts[1], parts[3].trim_end_matches(".json"), is_type, &content);
+            }
+        }
+    }
+
+    fn insert(&mut self, namespace: &str, name: &str, is_type: bool, content: &str) {
+        let json: Value = match serde_json::from_str(content) {
+            Ok(json) => json,
+            Err(err) => {
+                warn!("Invalid json in data pack for {}:{}: {err}", namespace, name);
+                return;
+            }
+        };
+
+        let id = format!("{}:{}", namespace, name);
+        match is_type {
+            true => self.dimension_types.insert(id, json),
+            false => self.dimensions.insert(id, json),
+        };
+    }
+}
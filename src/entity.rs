@@ -0,0 +1,243 @@
+//! ENTITIES
+//!
+//! Since 1.17 entities are no longer stored in the terrain chunks, they have their own region files
+//! in the `entities` directory using the same region format (see region.rs). Each chunk payload is
+//! a compound holding the data version, the chunk position as an int array of two, and the list of
+//! entities in that chunk.
+//!
+//! | TAG -->         | DataVersion | Position  |    Entities    |
+//! |-----------------|-------------|-----------|----------------|
+//! | TYPE -->        |     Int     | Int Array | List(Compound) |
+
+//! ENTITY FORMAT
+//!
+//! Every entity shares a common set of tags; the rest depend on the kind of entity. The UUID is
+//! stored as four ints, most significant first, and the position and rotation are lists of doubles
+//! and floats respectively. CustomName is a string of json until 1.21.5 stores the text component
+//! itself.
+//!
+//! | TAG -->         |   id   |    UUID   |     Pos      |  Rotation   |     CustomName     | Passengers |
+//! |-----------------|--------|-----------|--------------|-------------|--------------------|------------|
+//! | TYPE -->        | String | Int Array | List(Double) | List(Float) | String or Compound |    List    |
+
+use std::collections::HashMap;
+
+use crate::chunk::Chunk;
+use crate::item::Item;
+use crate::nbt::NBT;
+use crate::tag::{Tag, TagType};
+
+#[derive(Debug, Clone)]
+pub struct EntityChunk {
+    pub data_version: i32,
+    pub x: i32,
+    pub z: i32,
+    pub entities: Vec<Entity>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entity {
+    pub id: String,
+    pub uuid: String,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub custom_name: Option<String>,
+    pub data: EntityData,
+    pub passengers: Vec<Entity>,
+    pub raw: Tag,
+}
+
+#[derive(Debug, Clone)]
+pub enum EntityData {
+    Villager {
+        profession: String,
+        villager_type: String,
+        level: i32,
+    },
+    ItemFrame {
+        item: Option<Item>,
+        item_rotation: i8,
+    },
+    ArmorStand {
+        equipment: HashMap<String, Item>,
+    },
+    Unknown,
+}
+
+impl EntityChunk {
+    pub fn new(bytes: Vec<u8>, x: i32, z: i32) -> Self {
+        let mut entity_chunk = EntityChunk {
+            data_version: 0,
+            x: x >> 4,
+            z: z >> 4,
+            entities: vec![],
+        };
+
+        let raw_bytes = match bytes.len() {
+            0 => vec![],
+            _ => Chunk::decompress(bytes),
+        };
+
+        let nbt = NBT::new(&raw_bytes);
+
+        for tag in nbt.tags.subtags {
+            match tag.name.as_str() {
+                "DataVersion" => entity_chunk.data_version = tag.payload_int(),
+                "Position" => {
+                    let position = tag.payload_int_array();
+                    if 2 == position.len() {
+                        entity_chunk.x = position[0];
+                        entity_chunk.z = position[1];
+                    }
+                }
+                "Entities" => {
+                    for compound in tag.subtags {
+                        if TagType::Compound != compound.tagtype { continue }
+                        entity_chunk.entities.push(Entity::new(compound));
+                    }
+                }
+                _ => {} // nothing else is stored in entity chunks
+            }
+        }
+
+        entity_chunk
+    }
+}
+
+impl Entity {
+    pub fn new(tag: Tag) -> Self {
+        let mut entity = Entity {
+            id: String::new(),
+            uuid: String::new(),
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            custom_name: None,
+            data: EntityData::Unknown,
+            passengers: vec![],
+            raw: Tag::new(vec![0]),
+        };
+
+        for subtag in &tag.subtags {
+            match subtag.name.as_str() {
+                "id" => entity.id = subtag.payload_string(),
+                "UUID" => entity.uuid = uuid_string(subtag.payload_int_array()),
+                "Pos" => {
+                    if 3 == subtag.subtags.len() {
+                        entity.x = subtag.subtags[0].payload_double();
+                        entity.y = subtag.subtags[1].payload_double();
+                        entity.z = subtag.subtags[2].payload_double();
+                    }
+                }
+                "Rotation" => {
+                    if 2 == subtag.subtags.len() {
+                        entity.yaw = subtag.subtags[0].payload_float();
+                        entity.pitch = subtag.subtags[1].payload_float();
+                    }
+                }
+                "CustomName" => entity.custom_name = subtag.payload_text(),
+                "Passengers" => {
+                    for passenger in &subtag.subtags {
+                        entity.passengers.push(Entity::new(passenger.clone()));
+                    }
+                }
+                "VillagerData" => entity.data = Entity::process_villager(subtag),
+                _ => {} // type specific, handled below
+            }
+        }
+
+        match entity.id.as_str() {
+            "minecraft:item_frame" | "minecraft:glow_item_frame" => {
+                entity.data = Entity::process_item_frame(&tag);
+            }
+            "minecraft:armor_stand" => {
+                entity.data = Entity::process_armor_stand(&tag);
+            }
+            _ => {}
+        }
+
+        entity.raw = tag;
+
+        entity
+    }
+
+    fn process_villager(tag: &Tag) -> EntityData {
+        let mut profession = String::new();
+        let mut villager_type = String::new();
+        let mut level = 0;
+
+        for subtag in &tag.subtags {
+            match subtag.name.as_str() {
+                "profession" => profession = subtag.payload_string(),
+                "type" => villager_type = subtag.payload_string(),
+                "level" => level = subtag.payload_int(),
+                _ => {}
+            }
+        }
+
+        EntityData::Villager { profession, villager_type, level }
+    }
+
+    fn process_item_frame(tag: &Tag) -> EntityData {
+        let mut item = None;
+        let mut item_rotation = 0;
+
+        for subtag in &tag.subtags {
+            match subtag.name.as_str() {
+                "Item" => item = Some(Item::new(subtag)),
+                "ItemRotation" => item_rotation = subtag.payload_byte(),
+                _ => {}
+            }
+        }
+
+        EntityData::ItemFrame { item, item_rotation }
+    }
+
+    fn process_armor_stand(tag: &Tag) -> EntityData {
+        let mut equipment = HashMap::new();
+
+        for subtag in &tag.subtags {
+            // the legacy lists are ordered, the 1.21.5 compound is keyed by slot name
+            let slots = match subtag.name.as_str() {
+                "ArmorItems" => vec!["feet", "legs", "chest", "head"],
+                "HandItems" => vec!["mainhand", "offhand"],
+                "equipment" => subtag.subtags.iter().map(|slot| slot.name.as_str()).collect(),
+                _ => continue,
+            };
+
+            for (slot, compound) in slots.iter().zip(&subtag.subtags) {
+                let item = Item::new(compound);
+                if 0 == item.id.len() { continue }
+                equipment.insert(String::from(*slot), item);
+            }
+        }
+
+        EntityData::ArmorStand { equipment }
+    }
+
+    /// The chunk coordinates this entity is standing in
+    pub fn chunk(&self) -> (i32, i32) {
+        ((self.x.floor() as i32) >> 4, (self.z.floor() as i32) >> 4)
+    }
+
+    /// Whether the entity is inside the box spanned by the two corners, inclusive
+    pub fn within(&self, min: (f64, f64, f64), max: (f64, f64, f64)) -> bool {
+        min.0 <= self.x && self.x <= max.0
+            && min.1 <= self.y && self.y <= max.1
+            && min.2 <= self.z && self.z <= max.2
+    }
+}
+
+/// Format the four ints of an NBT UUID the way Minecraft writes them in file names
+pub fn uuid_string(ints: Vec<i32>) -> String {
+    if 4 != ints.len() { return String::new() }
+
+    let hex: String = ints.iter().map(|int| format!("{:08x}", *int as u32)).collect();
+
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
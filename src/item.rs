@@ -0,0 +1,70 @@
+//! ITEMS
+//!
+//! Item stacks show up anywhere something can hold an item: container block entities, lecterns,
+//! item frames, armor stands and player inventories. Each stack is a compound holding the item
+//! `id`, a stack count and, when it sits in an inventory, the `Slot` it occupies. Extra item data
+//! lives in the `tag` compound, which was replaced by `components` in 1.20.5.
+//!
+//! | TAG -->         |   id   |       Count / count        |  Slot  |  tag / components  |
+//! |-----------------|--------|----------------------------|--------|--------------------|
+//! | TYPE -->        | String | Byte (Int since 1.20.5)    |  Byte  |      Compound      |
+
+use crate::tag::{Tag, TagType};
+
+#[derive(Debug, Clone)]
+pub struct Item {
+    pub id: String,
+    pub count: i32,
+    pub slot: Option<i8>,
+    pub tag: Option<Tag>,
+}
+
+impl Item {
+    pub fn new(compound: &Tag) -> Self {
+        let mut item = Item {
+            id: String::new(),
+            count: 1,
+            slot: None,
+            tag: None,
+        };
+
+        for subtag in &compound.subtags {
+            match subtag.name.as_str() {
+                "id" => item.id = subtag.payload_string(),
+                "Count" => item.count = subtag.payload_byte() as i32,
+                "count" => item.count = subtag.payload_int(),
+                "Slot" => item.slot = Some(subtag.payload_byte()),
+                "tag" | "components" => item.tag = Some(subtag.clone()),
+                _ => {} // nothing else is stored on the stack itself
+            }
+        }
+
+        item
+    }
+
+    /// Collect every non-empty item stack from a list of item compounds
+    pub fn from_list(tags: &Vec<Tag>) -> Vec<Item> {
+        let mut items = vec![];
+
+        for tag in tags {
+            if TagType::Compound != tag.tagtype { continue }
+
+            let item = Item::new(tag);
+
+            // empty slots are stored as compounds without an id
+            if 0 == item.id.len() || "minecraft:air" == item.id { continue }
+
+            items.push(item);
+        }
+
+        items
+    }
+
+    /// Look up a compound stored in the item's `tag`/`components` data
+    pub fn data(&self, name: &str) -> Option<&Tag> {
+        match &self.tag {
+            Some(tag) => tag.subtags.iter().find(|subtag| subtag.name == name),
+            None => None,
+        }
+    }
+}
@@ -0,0 +1,412 @@
+//! MINECRAFT JARS
+//!
+//! Every launcher keeps client jars somewhere else. The official launcher puts one folder per
+//! version in `versions`, where Fabric and Forge profiles only hold a json pointing at the vanilla
+//! version they're built on through `inheritsFrom`. Prism and MultiMC share their jars between
+//! instances in a maven style `libraries` folder. Servers from 1.18 on bundle the real server jar
+//! inside the one that gets run, listed in `META-INF/versions.list`.
+//!
+//! | SOURCE -->      | where                                                  | jar                                                          |
+//! |-----------------|--------------------------------------------------------|--------------------------------------------------------------|
+//! | launcher -->    | .minecraft, ~/.var/app/com.mojang.Minecraft/.minecraft | versions/<id>/<id>.json, versions/<id>/<id>.jar              |
+//! | prism -->       | PrismLauncher, ~/.var/app/org.prismlauncher...         | libraries/com/mojang/minecraft/<v>/minecraft-<v>-client.jar  |
+//! | multimc -->     | MultiMC, ~/.local/share/multimc                        | libraries/com/mojang/minecraft/<v>/minecraft-<v>-client.jar  |
+//! | server -->      | next to the world folder                               | *.jar, META-INF/versions/<v>/server-<v>.jar                  |
+//!
+//! The jar picked is the one for the version the world was last played in, or failing that the
+//! closest newer one, since newer jars still have every older block's textures.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+use zip::read::ZipArchive;
+
+use crate::version::MinecraftVersion;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JarSource {
+    Launcher,
+    Prism,
+    MultiMC,
+    Server,
+}
+
+impl fmt::Display for JarSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            JarSource::Launcher => "launcher",
+            JarSource::Prism => "prism",
+            JarSource::MultiMC => "multimc",
+            JarSource::Server => "server",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JarCandidate {
+    pub path: PathBuf,
+    /// The server jar bundled inside `path`, if it's a bundler
+    pub entry: Option<String>,
+    pub version: MinecraftVersion,
+    pub source: JarSource,
+    /// Game directories of the profiles or instances playing this version
+    pub game_dirs: Vec<PathBuf>,
+}
+
+impl JarCandidate {
+    /// A path to the jar on disk, bundled server jars are copied out into the cache first
+    pub fn resolve(&self, cache_dir: &Path) -> io::Result<PathBuf> {
+        let entry = match &self.entry {
+            Some(entry) => entry,
+            None => return Ok(self.path.clone()),
+        };
+
+        let file_name = entry.rsplit('/').next().unwrap_or(entry);
+        let target = cache_dir.join("server").join(file_name);
+        if target.is_file() {
+            return Ok(target);
+        }
+
+        let mut archive = ZipArchive::new(File::open(&self.path)?)?;
+        let mut bytes = Vec::new();
+        archive.by_name(entry)?.read_to_end(&mut bytes)?;
+
+        fs::create_dir_all(cache_dir.join("server"))?;
+        fs::write(&target, bytes)?;
+        println!("extracted bundled server jar {:?} to {:?}", entry, &target);
+
+        Ok(target)
+    }
+
+    fn plays(&self, world_path: &Path) -> bool {
+        self.game_dirs.iter().any(|game_dir| world_path.starts_with(game_dir))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct JarLocator {
+    pub candidates: Vec<JarCandidate>,
+}
+
+impl JarLocator {
+    /// Look through every launcher install on this machine, and next to the given worlds for
+    /// server jars
+    pub fn discover(world_paths: &[PathBuf]) -> Self {
+        let mut locator = JarLocator::default();
+
+        for root in JarLocator::launcher_roots() {
+            locator.scan_launcher(&root);
+        }
+        for (source, root) in JarLocator::instance_roots() {
+            locator.scan_instances(source, &root);
+        }
+        for world_path in world_paths {
+            if let Some(server_dir) = world_path.parent() {
+                locator.scan_server(server_dir);
+            }
+        }
+
+        println!("found {:?} minecraft jars", locator.candidates.len());
+
+        locator
+    }
+
+    /// The best jar for a world last played in `target`, or the newest release without one. An
+    /// exact match wins, then the closest newer version, then the closest older one. Server jars
+    /// have no textures, so they're only used when no client jar was found at all.
+    pub fn select(&self, target: Option<&MinecraftVersion>, world_paths: &[PathBuf]) -> Option<&JarCandidate> {
+        let plays_world = |candidate: &JarCandidate| world_paths.iter().any(|path| candidate.plays(path));
+
+        let mut candidates: Vec<&JarCandidate> = self.candidates.iter()
+            .filter(|candidate| JarSource::Server != candidate.source)
+            .collect();
+        if candidates.is_empty() && !self.candidates.is_empty() {
+            log::warn!("only server jars found, blocks won't have textures");
+            candidates = self.candidates.iter().collect();
+        }
+
+        let target = match target {
+            Some(target) => target,
+            None => {
+                return candidates.into_iter()
+                    .filter(|candidate| candidate.version.is_release())
+                    .max_by(|a, b| a.version.cmp(&b.version));
+            }
+        };
+
+        let exact = candidates.iter()
+            .filter(|candidate| &candidate.version == target)
+            .min_by_key(|candidate| !plays_world(candidate));
+        if let Some(exact) = exact {
+            return Some(exact);
+        }
+
+        let newer = candidates.iter()
+            .filter(|candidate| &candidate.version > target)
+            .min_by(|a, b| a.version.cmp(&b.version));
+        if let Some(newer) = newer {
+            log::warn!("no {} jar found, using {} instead", target, newer.version);
+            return Some(newer);
+        }
+
+        let older = candidates.into_iter()
+            .max_by(|a, b| a.version.cmp(&b.version));
+        if let Some(older) = older {
+            log::warn!("no {} jar found, using the older {}, newer blocks won't have textures", target, older.version);
+        }
+
+        older
+    }
+
+    fn launcher_roots() -> Vec<PathBuf> {
+        let home = dirs::home_dir().unwrap_or_default();
+        let mut roots = vec![];
+
+        if cfg!(target_os = "windows") {
+            if let Some(app_data) = dirs::data_dir() {
+                roots.push(app_data.join(".minecraft"));
+            }
+        }
+        if cfg!(target_os = "linux") {
+            roots.push(home.join(".minecraft"));
+            roots.push(home.join(".var/app/com.mojang.Minecraft/.minecraft"));
+        }
+        if cfg!(target_os = "macos") {
+            roots.push(home.join("Library/Application Support/minecraft"));
+        }
+
+        roots.into_iter().filter(|root| root.is_dir()).collect()
+    }
+
+    fn instance_roots() -> Vec<(JarSource, PathBuf)> {
+        let home = dirs::home_dir().unwrap_or_default();
+        let mut roots = vec![];
+
+        if let Some(data_dir) = dirs::data_dir() {
+            roots.push((JarSource::Prism, data_dir.join("PrismLauncher")));
+            roots.push((JarSource::MultiMC, data_dir.join("multimc")));
+            roots.push((JarSource::MultiMC, data_dir.join("MultiMC")));
+        }
+        if cfg!(target_os = "linux") {
+            roots.push((JarSource::Prism, home.join(".var/app/org.prismlauncher.PrismLauncher/data/PrismLauncher")));
+        }
+        // MultiMC is usually unpacked wherever, the home directory is the best guess
+        roots.push((JarSource::MultiMC, home.join("MultiMC")));
+
+        roots.into_iter().filter(|(_, root)| root.is_dir()).collect()
+    }
+
+    fn scan_launcher(&mut self, root: &Path) {
+        let versions = root.join("versions");
+        let entries = match fs::read_dir(&versions) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+
+        let first = self.candidates.len();
+        for entry in entries.flatten() {
+            let id = String::from(entry.file_name().to_string_lossy());
+
+            // modded profiles point at the vanilla version they're built on
+            let (vanilla, jar) = match JarLocator::launcher_version(&versions, &id) {
+                Some(found) => found,
+                None => continue,
+            };
+            let version = match MinecraftVersion::parse(&vanilla) {
+                Some(version) => version,
+                None => continue,
+            };
+            if self.candidates[first..].iter().any(|candidate| candidate.path == jar) {
+                continue;
+            }
+
+            self.candidates.push(JarCandidate {
+                path: jar,
+                entry: None,
+                version,
+                source: JarSource::Launcher,
+                game_dirs: vec![],
+            });
+        }
+
+        // profiles with their own game directory tell which version plays the worlds in it
+        for (version_id, game_dir) in JarLocator::launcher_profiles(root) {
+            let vanilla = match version_id.as_str() {
+                "latest-release" => self.candidates[first..].iter()
+                    .filter(|candidate| candidate.version.is_release())
+                    .max_by(|a, b| a.version.cmp(&b.version))
+                    .map(|candidate| candidate.version.id.clone()),
+                "latest-snapshot" => self.candidates[first..].iter()
+                    .max_by(|a, b| a.version.cmp(&b.version))
+                    .map(|candidate| candidate.version.id.clone()),
+                _ => JarLocator::launcher_version(&versions, &version_id).map(|(vanilla, _)| vanilla),
+            };
+            let vanilla = match vanilla {
+                Some(vanilla) => vanilla,
+                None => continue,
+            };
+
+            for candidate in self.candidates[first..].iter_mut() {
+                if candidate.version.id == vanilla {
+                    candidate.game_dirs.push(game_dir.clone());
+                }
+            }
+        }
+    }
+
+    /// Follow a version's json down to the vanilla version and the jar it runs
+    fn launcher_version(versions: &Path, id: &str) -> Option<(String, PathBuf)> {
+        let mut id = String::from(id);
+
+        // a handful of hops is plenty, anything more is a loop
+        for _ in 0..8 {
+            let folder = versions.join(&id);
+            let json: Value = fs::read_to_string(folder.join(format!("{}.json", &id))).ok()
+                .and_then(|content| serde_json::from_str(&content).ok())?;
+
+            match json["inheritsFrom"].as_str() {
+                Some(parent) => id = String::from(parent),
+                None => {
+                    let jar_id = json["jar"].as_str().unwrap_or(&id);
+                    let jar = versions.join(jar_id).join(format!("{}.jar", jar_id));
+                    return match jar.is_file() {
+                        true => Some((id, jar)),
+                        false => None,
+                    };
+                }
+            }
+        }
+
+        None
+    }
+
+    /// The version and game directory of every profile in launcher_profiles.json
+    fn launcher_profiles(root: &Path) -> Vec<(String, PathBuf)> {
+        let json: Value = match fs::read_to_string(root.join("launcher_profiles.json")).ok()
+            .and_then(|content| serde_json::from_str(&content).ok()) {
+            Some(json) => json,
+            None => return vec![],
+        };
+        let profiles = match json["profiles"].as_object() {
+            Some(profiles) => profiles,
+            None => return vec![],
+        };
+
+        profiles.values()
+            .filter_map(|profile| {
+                let version_id = profile["lastVersionId"].as_str()?;
+                let game_dir = profile["gameDir"].as_str().map(PathBuf::from).unwrap_or(root.to_path_buf());
+                Some((String::from(version_id), game_dir))
+            })
+            .collect()
+    }
+
+    fn scan_instances(&mut self, source: JarSource, root: &Path) {
+        // jars are shared between instances
+        let first = self.candidates.len();
+        let libraries = root.join("libraries").join("com").join("mojang").join("minecraft");
+        for entry in fs::read_dir(&libraries).into_iter().flatten().flatten() {
+            let id = String::from(entry.file_name().to_string_lossy());
+            let jar = entry.path().join(format!("minecraft-{}-client.jar", &id));
+            let version = match MinecraftVersion::parse(&id) {
+                Some(version) if jar.is_file() => version,
+                _ => continue,
+            };
+
+            self.candidates.push(JarCandidate { path: jar, entry: None, version, source, game_dirs: vec![] });
+        }
+
+        // each instance says which minecraft it runs in mmc-pack.json
+        let mut instances: HashMap<String, Vec<PathBuf>> = HashMap::new();
+        for entry in fs::read_dir(root.join("instances")).into_iter().flatten().flatten() {
+            let json: Value = match fs::read_to_string(entry.path().join("mmc-pack.json")).ok()
+                .and_then(|content| serde_json::from_str(&content).ok()) {
+                Some(json) => json,
+                None => continue,
+            };
+            let minecraft = json["components"].as_array().into_iter().flatten()
+                .find(|component| Some("net.minecraft") == component["uid"].as_str())
+                .and_then(|component| component["version"].as_str());
+
+            if let Some(minecraft) = minecraft {
+                let game_dirs = instances.entry(String::from(minecraft)).or_default();
+                game_dirs.push(entry.path().join(".minecraft"));
+                game_dirs.push(entry.path().join("minecraft"));
+            }
+        }
+
+        for candidate in self.candidates[first..].iter_mut() {
+            if let Some(game_dirs) = instances.get(&candidate.version.id) {
+                candidate.game_dirs.extend(game_dirs.iter().cloned());
+            }
+        }
+    }
+
+    fn scan_server(&mut self, server_dir: &Path) {
+        for entry in fs::read_dir(server_dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if !path.is_file() || Some("jar") != path.extension().and_then(|extension| extension.to_str()) {
+                continue;
+            }
+            if self.candidates.iter().any(|candidate| candidate.path == path) {
+                continue;
+            }
+
+            let mut archive = match File::open(&path).map(ZipArchive::new) {
+                Ok(Ok(archive)) => archive,
+                _ => continue,
+            };
+
+            // bundlers list "<sha256>\t<id>\t<path>" for each jar in META-INF/versions
+            if let Some(versions_list) = read_entry(&mut archive, "META-INF/versions.list") {
+                for line in versions_list.lines() {
+                    let fields: Vec<&str> = line.split('\t').collect();
+                    let (id, jar) = match fields[..] {
+                        [_, id, jar] => (id, jar),
+                        _ => continue,
+                    };
+                    if let Some(version) = MinecraftVersion::parse(id) {
+                        self.candidates.push(JarCandidate {
+                            path: path.clone(),
+                            entry: Some(format!("META-INF/versions/{}", jar)),
+                            version,
+                            source: JarSource::Server,
+                            game_dirs: vec![server_dir.to_path_buf()],
+                        });
+                    }
+                }
+                continue;
+            }
+
+            // plain jars have had a version.json since 1.14, older servers only have their name
+            let id = match read_entry(&mut archive, "version.json")
+                .and_then(|content| serde_json::from_str::<Value>(&content).ok()) {
+                Some(json) => json["id"].as_str().map(String::from),
+                None => path.file_stem()
+                    .map(|stem| String::from(stem.to_string_lossy().trim_start_matches("minecraft_server."))),
+            };
+            if let Some(version) = id.as_deref().and_then(MinecraftVersion::parse) {
+                self.candidates.push(JarCandidate {
+                    path,
+                    entry: None,
+                    version,
+                    source: JarSource::Server,
+                    game_dirs: vec![server_dir.to_path_buf()],
+                });
+            }
+        }
+    }
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Option<String> {
+    let mut content = String::new();
+    archive.by_name(name).ok()?.read_to_string(&mut content).ok()?;
+    Some(content)
+}
@@ -0,0 +1,132 @@
+//! WORLD LAYOUTS
+//!
+//! Where the dimensions of a world end up depends on what's running it. Vanilla, Forge and Fabric
+//! keep everything inside the world directory, while Bukkit, Spigot and Paper split the nether and
+//! the end off into folders of their own next to it, each holding a vanilla style `DIM-1` or `DIM1`.
+//! Forge worlds from before 1.16 keep modded dimensions in numbered `DIM<id>` folders.
+//!
+//! | LAYOUT -->      | overworld | minecraft:the_nether  | minecraft:the_end    | modded                                  |
+//! |-----------------|-----------|-----------------------|----------------------|-----------------------------------------|
+//! | vanilla -->     | <world>   | <world>/DIM-1         | <world>/DIM1         | <world>/dimensions/<ns>/<n>             |
+//! | bukkit -->      | <world>   | <world>_nether/DIM-1  | <world>_the_end/DIM1 | <world>/dimensions/<ns>/<n>             |
+//! | forge -->       | <world>   | <world>/DIM-1         | <world>/DIM1         | <world>/dimensions/..., <world>/DIM<id> |
+//!
+//! Forge leaves an `fml` (or `FML`) compound in level.dat, Fabric a `data/fabricRegistry.dat`, and
+//! Bukkit a `uid.dat` in every world folder.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+use crate::dimension::{DataPackDimensions, Dimension};
+use crate::nbt::NBT;
+use crate::source::{join, WorldSource};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayoutKind {
+    Vanilla,
+    Bukkit,
+    Forge,
+    Fabric,
+}
+
+impl fmt::Display for LayoutKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LayoutKind::Vanilla => "vanilla",
+            LayoutKind::Bukkit => "bukkit",
+            LayoutKind::Forge => "forge",
+            LayoutKind::Fabric => "fabric",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One logical world, even when its dimensions are spread over several folders
+#[derive(Debug)]
+pub struct WorldLayout {
+    pub kind: LayoutKind,
+    /// The world's own folder, holding level.dat and the overworld
+    pub source: Arc<WorldSource>,
+    /// Folders next to the world's own holding other dimensions, by dimension id
+    pub siblings: BTreeMap<String, Arc<WorldSource>>,
+}
+
+impl WorldLayout {
+    /// Open a world directory or backup and work out where its dimensions are kept
+    pub fn detect(world_path: &str) -> io::Result<Self> {
+        let source = WorldSource::open(world_path)?;
+
+        let mut siblings = BTreeMap::new();
+        for (id, suffix) in [("minecraft:the_nether", "_nether"), ("minecraft:the_end", "_the_end")] {
+            let directory = Dimension::directory(id);
+            let sibling = match source.sibling(suffix) {
+                Some(sibling) => sibling,
+                None => continue,
+            };
+
+            // the sibling keeps the dimension in the same place a vanilla world would
+            if sibling.is_dir(&join(&directory, "region")) {
+                siblings.insert(String::from(id), Arc::new(sibling));
+            }
+        }
+
+        let kind = match 0 < siblings.len() || source.is_file("uid.dat") {
+            true => LayoutKind::Bukkit,
+            false => WorldLayout::modded_kind(&source),
+        };
+
+        let layout = WorldLayout { kind, source: Arc::new(source), siblings };
+        println!("detected {} world layout with {:?} sibling folders", layout.kind, layout.siblings.len());
+
+        Ok(layout)
+    }
+
+    fn modded_kind(source: &WorldSource) -> LayoutKind {
+        if source.is_file("data/fabricRegistry.dat") {
+            return LayoutKind::Fabric;
+        }
+
+        let level = match source.read("level.dat") {
+            Ok(bytes) => NBT::from_bytes(bytes),
+            Err(_) => return LayoutKind::Vanilla,
+        };
+        match level.tags.subtags.iter().any(|tag| "fml" == tag.name || "FML" == tag.name) {
+            true => LayoutKind::Forge,
+            false => LayoutKind::Vanilla,
+        }
+    }
+
+    /// Every dimension with region files, wherever it's kept. A dimension in a sibling folder wins
+    /// over a leftover copy inside the world directory, since that's the one the server uses.
+    /// Dimension types always come from the world's own data packs, siblings only hold regions.
+    pub fn dimensions(&self, data_version: i32) -> Vec<Dimension> {
+        let types = DataPackDimensions::load(&self.source, data_version);
+        let mut dimensions: Vec<Dimension> = Dimension::discover(&self.source, &types, data_version).into_iter()
+            .filter(|dimension| !self.siblings.contains_key(&dimension.id))
+            .collect();
+
+        for (id, sibling) in &self.siblings {
+            if let Some(dimension) = Dimension::find(sibling, id, &types, data_version) {
+                dimensions.push(dimension);
+            }
+        }
+
+        dimensions
+    }
+
+    /// Look up a single dimension by the name used in the config, e.g. "nether" or "mypack:mining"
+    pub fn find(&self, dimension: &str, data_version: i32) -> Option<Dimension> {
+        let directory = Dimension::directory(dimension);
+        let types = DataPackDimensions::load(&self.source, data_version);
+
+        for (id, sibling) in &self.siblings {
+            if Dimension::directory(id) == directory {
+                return Dimension::find(sibling, id, &types, data_version);
+            }
+        }
+
+        Dimension::find(&self.source, dimension, &types, data_version)
+    }
+}
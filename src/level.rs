@@ -1,7 +1,221 @@
 //! The level.dat file contains global information about the world such as the time of day, the
 //! singleplayer player, the level generator used, and the seed.
 //! https://minecraft.wiki/w/Java_Edition_level_format
+
+//! LEVEL.DAT
+//!
+//! The file is a gzipped NBT compound with a single `Data` compound inside of it. The seed moved
+//! into `WorldGenSettings` in 1.16, older worlds keep it in `RandomSeed`. Game rule values are all
+//! stored as strings, e.g. "true" or "3".
+//!
+//! | TAG -->         | LevelName |      Version       | DataVersion | WorldGenSettings / RandomSeed |
+//! |-----------------|-----------|--------------------|-------------|-------------------------------|
+//! | TYPE -->        |  String   | Compound(Id, Name) |     Int     |     Compound(seed) / Long     |
+//!
+//! | TAG -->         | SpawnX/Y/Z | Time / DayTime | Difficulty | GameRules | BorderCenterX/Z, BorderSize |
+//! |-----------------|------------|----------------|------------|-----------|-----------------------------|
+//! | TYPE -->        |    Int     |      Long      |    Byte    | Compound  |           Double            |
+
+use std::collections::HashMap;
+
+use crate::chunk::Biomes;
+use crate::nbt::NBT;
+use crate::source::WorldSource;
+use crate::tag::{Tag, TagType};
+use crate::version::MinecraftVersion;
+
 #[derive(Debug)]
 pub struct Level {
+    pub level_name: String,
+    pub version_name: String,
+    pub version_id: i32,
+    pub data_version: i32,
+    pub seed: i64,
+    pub biome_zoom_seed: i64,
+    pub spawn_x: i32,
+    pub spawn_y: i32,
+    pub spawn_z: i32,
+    pub spawn_angle: f32,
+    pub time: i64,
+    pub day_time: i64,
+    pub difficulty: i8,
+    pub difficulty_locked: bool,
+    pub hardcore: bool,
+    pub game_type: i32,
+    pub game_rules: HashMap<String, String>,
+    pub border_center_x: f64,
+    pub border_center_z: f64,
+    pub border_size: f64,
+    pub enabled_data_packs: Vec<String>,
+    pub disabled_data_packs: Vec<String>,
+    pub last_played: i64,
+    pub player: Option<Tag>,
+}
+
+impl Level {
+    pub fn new() -> Self {
+        Level {
+            level_name: String::new(),
+            version_name: String::new(),
+            version_id: 0,
+            data_version: 0,
+            seed: 0,
+            biome_zoom_seed: Biomes::zoom_seed(0),
+            spawn_x: 0,
+            spawn_y: 0,
+            spawn_z: 0,
+            spawn_angle: 0.0,
+            time: 0,
+            day_time: 0,
+            difficulty: 2,
+            difficulty_locked: false,
+            hardcore: false,
+            game_type: 0,
+            game_rules: HashMap::new(),
+            border_center_x: 0.0,
+            border_center_z: 0.0,
+            border_size: 59999968.0,
+            enabled_data_packs: vec![],
+            disabled_data_packs: vec![],
+            last_played: 0,
+            player: None,
+        }
+    }
+
+    pub fn load(level_path: &str) -> Self {
+        Level::from_nbt(NBT::from_file(level_path))
+    }
+
+    pub fn from_nbt(nbt: NBT) -> Self {
+        let mut level = Level::new();
+
+        for tag in nbt.tags.subtags {
+            if "Data" == tag.name {
+                level.process_data(tag.subtags);
+            }
+        }
+
+        // hashed once here rather than on every smoothed biome lookup
+        level.biome_zoom_seed = Biomes::zoom_seed(level.seed);
+
+        println!("loaded level data for {:?} ({})", &level.level_name, &level.version_name);
+
+        level
+    }
+
+    /// The version the world was last played in, worked out from the data version when the name
+    /// isn't one we can read
+    pub fn version(&self) -> Option<MinecraftVersion> {
+        MinecraftVersion::parse(&self.version_name)
+            .or_else(|| MinecraftVersion::from_data_version(self.data_version))
+    }
+
+    /// The data version of a world without loading all of level.dat into a Level, 0 if unknown
+    pub fn data_version_of(source: &WorldSource) -> i32 {
+        let nbt = match source.read("level.dat") {
+            Ok(bytes) => NBT::from_bytes(bytes),
+            Err(_) => return 0,
+        };
+        for tag in nbt.tags.subtags {
+            if "Data" != tag.name { continue }
+            for subtag in tag.subtags {
+                if "DataVersion" == subtag.name { return subtag.payload_int() }
+            }
+        }
+
+        0
+    }
+
+    /// The data packs a world has enabled, lowest priority first, without loading all of level.dat
+    /// into a Level. None if level.dat doesn't list any, as in worlds from before 1.13.
+    pub fn enabled_data_packs_of(source: &WorldSource) -> Option<Vec<String>> {
+        let nbt = NBT::from_bytes(source.read("level.dat").ok()?);
+        let data = nbt.tags.subtags.into_iter().find(|tag| "Data" == tag.name)?;
+        let data_packs = data.get("DataPacks")?;
+        let enabled = data_packs.get("Enabled")?;
+
+        Some(enabled.subtags.iter().map(|pack| pack.payload_string()).collect())
+    }
+
+    fn process_data(&mut self, tags: Vec<Tag>) {
+        for tag in tags {
+            match tag.name.as_str() {
+                "LevelName" => self.level_name = tag.payload_string(),
+                "Version" => {
+                    for subtag in &tag.subtags {
+                        match subtag.name.as_str() {
+                            "Id" => self.version_id = subtag.payload_int(),
+                            "Name" => self.version_name = subtag.payload_string(),
+                            _ => {}
+                        }
+                    }
+                }
+                "DataVersion" => self.data_version = tag.payload_int(),
+                "WorldGenSettings" => {
+                    for subtag in &tag.subtags {
+                        if "seed" == subtag.name {
+                            self.seed = subtag.payload_long();
+                        }
+                    }
+                }
+                "RandomSeed" => self.seed = tag.payload_long(),
+                "SpawnX" => self.spawn_x = tag.payload_int(),
+                "SpawnY" => self.spawn_y = tag.payload_int(),
+                "SpawnZ" => self.spawn_z = tag.payload_int(),
+                "SpawnAngle" => self.spawn_angle = tag.payload_float(),
+                "Time" => self.time = tag.payload_long(),
+                "DayTime" => self.day_time = tag.payload_long(),
+                "Difficulty" => self.difficulty = tag.payload_byte(),
+                "DifficultyLocked" => self.difficulty_locked = 0 != tag.payload_byte(),
+                "hardcore" => self.hardcore = 0 != tag.payload_byte(),
+                "GameType" => self.game_type = tag.payload_int(),
+                "GameRules" => {
+                    for subtag in &tag.subtags {
+                        if TagType::String != subtag.tagtype { continue }
+                        self.game_rules.insert(subtag.name.clone(), subtag.payload_string());
+                    }
+                }
+                "BorderCenterX" => self.border_center_x = tag.payload_double(),
+                "BorderCenterZ" => self.border_center_z = tag.payload_double(),
+                "BorderSize" => self.border_size = tag.payload_double(),
+                "DataPacks" => {
+                    for subtag in &tag.subtags {
+                        let packs: Vec<String> = subtag.subtags.iter()
+                            .map(|pack| pack.payload_string())
+                            .collect();
+
+                        match subtag.name.as_str() {
+                            "Enabled" => self.enabled_data_packs = packs,
+                            "Disabled" => self.disabled_data_packs = packs,
+                            _ => {}
+                        }
+                    }
+                }
+                "LastPlayed" => self.last_played = tag.payload_long(),
+                "Player" => self.player = Some(tag),
+                _ => {} // plenty of other settings we have no use for
+            }
+        }
+    }
+
+    pub fn difficulty_name(&self) -> &str {
+        match self.difficulty {
+            0 => "peaceful",
+            1 => "easy",
+            2 => "normal",
+            3 => "hard",
+            _ => "unknown",
+        }
+    }
 
-}
\ No newline at end of file
+    /// The four corners of the world border as (min x, min z, max x, max z)
+    pub fn border_bounds(&self) -> (f64, f64, f64, f64) {
+        let radius = self.border_size / 2.0;
+        (
+            self.border_center_x - radius,
+            self.border_center_z - radius,
+            self.border_center_x + radius,
+            self.border_center_z + radius,
+        )
+    }
+}
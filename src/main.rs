@@ -1,20 +1,64 @@
+mod analysis;
 mod world;
+mod dimension;
 mod level;
 mod region;
 mod chunk;
+mod registry;
 mod tag;
 mod nbt;
+mod item;
+mod entity;
+mod poi;
+mod player;
 mod config;
 mod error;
 mod args;
 mod textures;
+mod workers;
+mod source;
+mod layout;
+mod version;
+mod launcher;
+
+use std::path::PathBuf;
+use std::process::exit;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
 
 use crate::world::*;
+use crate::analysis::{BlockStatistics, ReportFormat};
 use crate::args::*;
+use crate::chunk::set_strict_parsing;
 use crate::config::Config;
-use crate::textures::TexturePack;
+use crate::layout::WorldLayout;
+use crate::version::MinecraftVersion;
+use crate::textures::{PackStack, TexturePack};
+use crate::workers::set_threads;
+
+/// Prints warnings and errors from the log crate to stderr
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Logger = Logger;
 
 fn main() {
+    // route log warnings to stderr
+    let _ = log::set_logger(&LOGGER).map(|()| log::set_max_level(LevelFilter::Warn));
+
     // parse command line arguments
     let args: Args = ArgParse::load();
 
@@ -22,10 +66,29 @@ fn main() {
     let config = Config::load(&args.config_file);
     dbg!(&config);
 
+    // unknown chunk fields stop everything in strict mode, otherwise they're collected
+    set_strict_parsing(config.strict_parsing);
+
+    // chunks are decoded across this many threads, the command line wins over the config
+    set_threads(args.threads.unwrap_or(config.threads));
+
+    // the analysis subcommand reports on a world instead of rendering it
+    if Command::Analyze == args.command {
+        analyze(&args, &config);
+        return;
+    }
+
     // load textures
     let textures = TexturePack::load(config.textures.clone());
     dbg!(&textures);
 
+    // stack each render's packs and report which pack every asset comes from
+    for render in &config.renders {
+        let version = render.version.as_deref().and_then(MinecraftVersion::parse);
+        let stack = PackStack::new(&render.textures, version.as_ref());
+        stack.write_report(&config.output_dir.join(format!("{} packs.csv", render.title)));
+    }
+
     // collect world data
     // let world = World::new(&config.world_dir);
 
@@ -33,6 +96,54 @@ fn main() {
     println!("Baby's first Minecraft parser finished successfully!")
 }
 
+fn analyze(args: &Args, config: &Config) {
+    let world = match &args.world {
+        Some(world) => world.clone(),
+        None => {
+            eprintln!("Error: analyze needs a world, use --world <name or path>");
+            exit(40)
+        }
+    };
+
+    // world names from the config take priority over paths
+    let world_path = match config.worlds.get(&world) {
+        Some(world_path) => world_path.clone(),
+        None => world,
+    };
+
+    let format = match ReportFormat::new(&args.format) {
+        Some(format) => format,
+        None => {
+            eprintln!("Error: unknown report format {:?}, use csv or json", &args.format);
+            exit(41)
+        }
+    };
+
+    let output_path = match &args.output {
+        Some(output) => PathBuf::from(output),
+        None => PathBuf::from(format!("block_statistics.{}", args.format.to_lowercase())),
+    };
+
+    let layout = match WorldLayout::detect(&world_path) {
+        Ok(layout) => layout,
+        Err(err) => {
+            eprintln!("Error opening world ({:?}): {err}", &world_path);
+            exit(44)
+        }
+    };
+
+    let dimension = match layout.find(&args.dimension, level::Level::data_version_of(&layout.source)) {
+        Some(dimension) => dimension,
+        None => {
+            eprintln!("Error: no region files found for dimension {:?} in {:?}", &args.dimension, &world_path);
+            exit(43)
+        }
+    };
+
+    let statistics = BlockStatistics::collect(&dimension, &args.blocks);
+    statistics.write(format, &output_path);
+}
+
 fn texture_path_valid(target: &str) {
     let texture_path = String::from(target);
     dbg!(&texture_path);
@@ -1,3 +1,9 @@
+use std::fs;
+use std::io::Read;
+use std::process::exit;
+
+use flate2::read::GzDecoder;
+
 use crate::tag::*;
 
 #[derive(Debug)]
@@ -12,4 +18,39 @@ impl NBT {
             _ => NBT { tags: Tag::new(bytes.clone()) },
         }
     }
-}
\ No newline at end of file
+
+    /// Read a standalone NBT file such as level.dat, which is usually gzipped
+    pub fn from_file(file_path: &str) -> Self {
+        let bytes = match fs::read(file_path) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                eprintln!("Error reading NBT file ({:?}): {err}", &file_path);
+                exit(31)
+            }
+        };
+
+        NBT::from_bytes(bytes)
+    }
+
+    /// Parse the contents of a standalone NBT file, gzipped or not
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        NBT::new(&NBT::decompress(bytes))
+    }
+
+    /// Gunzip the bytes if they start with the gzip magic number, otherwise leave them be
+    pub fn decompress(bytes: Vec<u8>) -> Vec<u8> {
+        if bytes.len() < 2 || [0x1f, 0x8b] != bytes[0..2] {
+            return bytes;
+        }
+
+        let mut decompressed: Vec<u8> = vec![];
+        let mut decoder = GzDecoder::new(&bytes as &[u8]);
+        match decoder.read_to_end(&mut decompressed) {
+            Ok(_) => decompressed,
+            Err(err) => {
+                eprintln!("Error decompressing NBT file: {err}");
+                exit(37)
+            }
+        }
+    }
+}
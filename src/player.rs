@@ -0,0 +1,226 @@
+//! PLAYER DATA
+//!
+//! Each player that has joined the world has a gzipped NBT file in the `playerdata` directory named
+//! after their UUID, e.g. `playerdata/069a79f4-44e9-4726-a5be-fca90e38aaf5.dat`. The singleplayer
+//! player is also stored in level.dat under `Data.Player` using the same format. The respawn point
+//! moved from the separate `SpawnX/Y/Z` tags into a `respawn` compound in 1.21.5.
+//!
+//! | TAG -->         |     Pos      |  Dimension  |  SpawnX/Y/Z / respawn  | Inventory | EnderItems | Health |
+//! |-----------------|--------------|-------------|------------------------|-----------|------------|--------|
+//! | TYPE -->        | List(Double) |   String    |    Int / Compound      |   List    |    List    | Float  |
+
+//! USER CACHE
+//!
+//! Player files don't store the player's name. Servers keep a `usercache.json` next to the world
+//! directory which maps the UUIDs of recently seen players to their names.
+//!
+//! ```json
+//! [{"name": "Notch", "uuid": "069a79f4-44e9-4726-a5be-fca90e38aaf5", "expiresOn": "..."}]
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde_derive::Deserialize;
+
+use crate::entity::uuid_string;
+use crate::item::Item;
+use crate::nbt::NBT;
+use crate::source::WorldSource;
+use crate::tag::{Tag, TagType};
+
+#[derive(Debug, Clone)]
+pub struct Player {
+    pub uuid: String,
+    pub name: Option<String>,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub dimension: String,
+    pub spawn: Option<PlayerSpawn>,
+    pub inventory: Vec<Item>,
+    pub ender_chest: Vec<Item>,
+    pub health: f32,
+    pub last_seen: i64,
+    pub raw: Tag,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerSpawn {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub dimension: String,
+    pub forced: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserCacheEntry {
+    pub name: String,
+    pub uuid: String,
+}
+
+impl Player {
+    pub fn new(tag: Tag) -> Self {
+        let mut player = Player {
+            uuid: String::new(),
+            name: None,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            dimension: String::from("minecraft:overworld"),
+            spawn: None,
+            inventory: vec![],
+            ender_chest: vec![],
+            health: 20.0,
+            last_seen: 0,
+            raw: Tag::new(vec![0]),
+        };
+
+        let mut spawn = PlayerSpawn {
+            x: 0,
+            y: 0,
+            z: 0,
+            dimension: String::from("minecraft:overworld"),
+            forced: false,
+        };
+        let mut has_spawn = false;
+
+        for subtag in &tag.subtags {
+            match subtag.name.as_str() {
+                "UUID" => player.uuid = uuid_string(subtag.payload_int_array()),
+                "Pos" => {
+                    if 3 == subtag.subtags.len() {
+                        player.x = subtag.subtags[0].payload_double();
+                        player.y = subtag.subtags[1].payload_double();
+                        player.z = subtag.subtags[2].payload_double();
+                    }
+                }
+                "Rotation" => {
+                    if 2 == subtag.subtags.len() {
+                        player.yaw = subtag.subtags[0].payload_float();
+                        player.pitch = subtag.subtags[1].payload_float();
+                    }
+                }
+                "Dimension" => player.dimension = Player::dimension_name(subtag),
+                "Inventory" => player.inventory = Item::from_list(&subtag.subtags),
+                "EnderItems" => player.ender_chest = Item::from_list(&subtag.subtags),
+                "Health" => player.health = subtag.payload_float(),
+                // respawn point up to 1.21.4
+                "SpawnX" => { spawn.x = subtag.payload_int(); has_spawn = true; }
+                "SpawnY" => spawn.y = subtag.payload_int(),
+                "SpawnZ" => spawn.z = subtag.payload_int(),
+                "SpawnDimension" => spawn.dimension = Player::dimension_name(subtag),
+                "SpawnForced" => spawn.forced = 0 != subtag.payload_byte(),
+                // respawn point since 1.21.5
+                "respawn" => {
+                    has_spawn = true;
+                    for field in &subtag.subtags {
+                        match field.name.as_str() {
+                            "pos" => {
+                                let pos = field.payload_int_array();
+                                if 3 == pos.len() {
+                                    spawn.x = pos[0];
+                                    spawn.y = pos[1];
+                                    spawn.z = pos[2];
+                                }
+                            }
+                            "dimension" => spawn.dimension = field.payload_string(),
+                            "forced" => spawn.forced = 0 != field.payload_byte(),
+                            _ => {}
+                        }
+                    }
+                }
+                // bukkit and paper servers record when the player was last online
+                "bukkit" | "Paper" => {
+                    for field in &subtag.subtags {
+                        match field.name.as_str() {
+                            "lastPlayed" | "LastSeen" => player.last_seen = field.payload_long() / 1000,
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if has_spawn {
+            player.spawn = Some(spawn);
+        }
+
+        player.raw = tag;
+
+        player
+    }
+
+    /// Load a `playerdata/<uuid>.dat` file from the world
+    pub fn load(source: &WorldSource, player_path: &str) -> Option<Self> {
+        let bytes = match source.read(player_path) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                eprintln!("Error reading player file ({:?}): {err}", &player_path);
+                return None;
+            }
+        };
+        let mut player = Player::new(NBT::from_bytes(bytes).tags);
+
+        // older files may not carry their own UUID, but the file name always does
+        if 0 == player.uuid.len() {
+            if let Some(stem) = Path::new(player_path).file_stem() {
+                player.uuid = stem.to_string_lossy().to_string();
+            }
+        }
+
+        // vanilla doesn't store a timestamp, so fall back to when the file was last written
+        if 0 == player.last_seen {
+            player.last_seen = source.modified(player_path).unwrap_or(0);
+        }
+
+        Some(player)
+    }
+
+    /// Dimensions were stored as numeric ids before 1.16
+    fn dimension_name(tag: &Tag) -> String {
+        match tag.tagtype {
+            TagType::String => tag.payload_string(),
+            _ => match tag.payload_int() {
+                -1 => String::from("minecraft:the_nether"),
+                1 => String::from("minecraft:the_end"),
+                _ => String::from("minecraft:overworld"),
+            },
+        }
+    }
+
+    /// Read the server's usercache.json into a map of UUID to player name
+    pub fn load_user_cache(cache_path: &Path) -> HashMap<String, String> {
+        let content = match fs::read_to_string(cache_path) {
+            Ok(content) => content,
+            Err(_) => return HashMap::new(), // no cache means no names, which is fine
+        };
+
+        Player::parse_user_cache(&content)
+    }
+
+    pub fn parse_user_cache(content: &str) -> HashMap<String, String> {
+        let mut names = HashMap::new();
+
+        let entries: Vec<UserCacheEntry> = match serde_json::from_str(content) {
+            Ok(entries) => entries,
+            Err(err) => {
+                eprintln!("Error parsing user cache: {err}");
+                return names;
+            }
+        };
+
+        for entry in entries {
+            names.insert(entry.uuid.to_lowercase(), entry.name);
+        }
+
+        names
+    }
+}
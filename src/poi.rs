@@ -0,0 +1,113 @@
+//! POINTS OF INTEREST
+//!
+//! Points of interest are blocks that mobs look for: villager workstations, beds, bells, bee nests,
+//! lightning rods and nether portals. They are kept in the `poi` directory using the same region
+//! format as the terrain (see region.rs). Each chunk payload holds a compound of sections keyed by
+//! the section's y index as a string, and every section holds its list of records.
+//!
+//! | TAG -->         | DataVersion |                Sections                |
+//! |-----------------|-------------|----------------------------------------|
+//! | TYPE -->        |     Int     | Compound("<y>": { Valid, Records[] })  |
+
+//! POI RECORDS
+//!
+//! Each record names the type of point of interest, its absolute block position and how many more
+//! mobs can claim it. Beds and workstations have a single ticket, so a claimed one has none left.
+//!
+//! | TAG -->         |  type  |    pos    | free_tickets |
+//! |-----------------|--------|-----------|--------------|
+//! | TYPE -->        | String | Int Array |     Int      |
+
+use crate::chunk::Chunk;
+use crate::nbt::NBT;
+use crate::tag::{Tag, TagType};
+
+#[derive(Debug, Clone)]
+pub struct PointOfInterest {
+    pub poi_type: String,
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    pub free_tickets: i32,
+    pub valid: bool,
+}
+
+impl PointOfInterest {
+    /// Read every record out of a compressed poi chunk payload
+    pub fn from_chunk(bytes: Vec<u8>) -> Vec<PointOfInterest> {
+        let mut records = vec![];
+
+        let raw_bytes = match bytes.len() {
+            0 => return records,
+            _ => Chunk::decompress(bytes),
+        };
+
+        let nbt = NBT::new(&raw_bytes);
+
+        for tag in nbt.tags.subtags {
+            if "Sections" != tag.name { continue }
+
+            for section in tag.subtags {
+                if TagType::Compound != section.tagtype { continue }
+                records.extend(PointOfInterest::process_section(section));
+            }
+        }
+
+        records
+    }
+
+    fn process_section(section: Tag) -> Vec<PointOfInterest> {
+        let mut records = vec![];
+        let mut valid = true;
+
+        for subtag in &section.subtags {
+            if "Valid" == subtag.name {
+                valid = 0 != subtag.payload_byte();
+            }
+        }
+
+        for subtag in section.subtags {
+            if "Records" != subtag.name { continue }
+
+            for compound in subtag.subtags {
+                let mut poi = PointOfInterest {
+                    poi_type: String::new(),
+                    x: 0,
+                    y: 0,
+                    z: 0,
+                    free_tickets: 0,
+                    valid,
+                };
+
+                for field in compound.subtags {
+                    match field.name.as_str() {
+                        "type" => poi.poi_type = field.payload_string(),
+                        "pos" => {
+                            let pos = field.payload_int_array();
+                            if 3 == pos.len() {
+                                poi.x = pos[0];
+                                poi.y = pos[1];
+                                poi.z = pos[2];
+                            }
+                        }
+                        "free_tickets" => poi.free_tickets = field.payload_int(),
+                        _ => {}
+                    }
+                }
+
+                records.push(poi);
+            }
+        }
+
+        records
+    }
+
+    /// Whether a villager has claimed this bed, workstation or meeting point
+    pub fn is_claimed(&self) -> bool {
+        match self.poi_type.as_str() {
+            "minecraft:nether_portal" | "minecraft:lodestone" | "minecraft:lightning_rod"
+            | "minecraft:bee_nest" | "minecraft:beehive" => false,
+            _ => 0 == self.free_tickets,
+        }
+    }
+}
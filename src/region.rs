@@ -32,20 +32,34 @@
 //! |-----------------|---|---|---|---|
 //! | DESCRIPTION --> |   timestamp   |
 
-use std::{fs::File};
+use std::fs;
 use std::collections::HashMap;
-use std::io::{Read, Seek, SeekFrom};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use crate::chunk::*;
+use crate::dimension::DimensionType;
+use crate::registry::BlockStateRegistry;
+use crate::source::WorldSource;
+use crate::workers::{map_parallel, threads};
 
 #[derive(Debug)]
 pub struct Region {
     pub(crate) region_path: String,
     region_headers: HashMap<i32, RegionHeader>,
+    /// The region file as load_headers read it, chunk payloads are cut out of it so the file is
+    /// only read once, and chunks that weren't edited are saved from it as they were
+    bytes: Vec<u8>,
     pub chunks: Vec<Chunk>,
     pub region_x: i32,
     pub x: i32,
     pub region_z: i32,
     pub z: i32,
+    pub registry: Arc<BlockStateRegistry>,
+    pub dimension_type: Arc<DimensionType>,
+    /// Where the region file is read from, region_path is relative to it
+    pub source: Arc<WorldSource>,
 }
 
 #[derive(Debug)]
@@ -57,35 +71,184 @@ pub struct RegionHeader {
 }
 
 pub trait RegionLoader {
-    fn new(region_path: &str) -> Self;
+    fn new(region_path: &str, registry: Arc<BlockStateRegistry>, dimension_type: Arc<DimensionType>) -> Self;
+    fn open(region_path: &str, registry: Arc<BlockStateRegistry>) -> Self;
+    fn load_headers(&mut self);
+    fn chunk_payloads(&self) -> Vec<(i32, i32, Vec<u8>)>;
     fn load_chunks(&mut self);
 }
 
 impl RegionLoader for Region {
-    fn new(region_path: &str) -> Self {
-        let filename_parts: Vec<&str> = region_path.split(".").collect();
-        let region_x = filename_parts[1].parse::<i32>().unwrap();
-        let region_z = filename_parts[2].parse::<i32>().unwrap();
-        let mut region = Region {
+    fn new(region_path: &str, registry: Arc<BlockStateRegistry>, dimension_type: Arc<DimensionType>) -> Self {
+        let mut region = Region::open(region_path, registry);
+        region.dimension_type = dimension_type;
+        region.load_chunks();
+        region
+    }
+
+    fn open(region_path: &str, registry: Arc<BlockStateRegistry>) -> Self {
+        let (region_x, region_z) = Region::coordinates(region_path).unwrap();
+        Region {
             region_path: String::from(region_path),
             region_headers: HashMap::new(),
+            bytes: Vec::new(),
             chunks: Vec::new(),
             region_x: region_x,
             x: region_x * 512,
             region_z: region_z,
             z: region_z * 512,
+            registry,
+            dimension_type: Arc::new(DimensionType::overworld()),
+            source: Arc::new(WorldSource::local()),
+        }
+    }
+
+    fn load_headers(&mut self) {
+        self.bytes = match self.source.read(&self.region_path) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                println!("Failed to read region file {:?}: {:?}", &self.region_path, err);
+                vec![]
+            }
         };
-        region.load_chunks();
-        region
+
+        self.parse_headers();
     }
 
+    /// The still-compressed payload of every chunk slot along with its block x/z coordinates, cut
+    /// out of the file read by load_headers
+    fn chunk_payloads(&self) -> Vec<(i32, i32, Vec<u8>)> {
+        let mut payloads = vec![];
+
+        // in slot order so every run hands out the chunks the same way
+        let mut slots: Vec<(&i32, &RegionHeader)> = self.region_headers.iter().collect();
+        slots.sort_by_key(|(r, _)| **r);
+
+        for (r, region_header) in slots {
+            let chunk_buffer = match self.payload(region_header) {
+                Some(chunk_buffer) => chunk_buffer.to_vec(),
+                None => {
+                    println!("Failed to read chunk bytes: slot {:?} lies past the end of {:?}", r, &self.region_path);
+                    vec![]
+                }
+            };
+            let x = self.x + (r % 32 * 16);
+            let z = self.z + (r / 32 * 16);
+            payloads.push((x, z, chunk_buffer));
+        }
+
+        payloads
+    }
 
     fn load_chunks(&mut self) {
-        let mut region_file = File::open(&self.region_path).unwrap();
-        let mut location_buffer = vec![0u8; 4096];
-        let _ = region_file.read_exact(&mut location_buffer);
-        let mut updated_buffer = vec![0u8; 4096];
-        let _ = region_file.read_exact(&mut updated_buffer);
+        self.load_headers();
+
+        // decompressing and parsing each chunk is independent, so it's spread over the workers
+        let (registry, dimension_type) = (self.registry.clone(), self.dimension_type.clone());
+        self.chunks = map_parallel(self.chunk_payloads(), |(x, z, chunk_buffer)| {
+            Chunk::new(chunk_buffer, x, z, registry.clone(), dimension_type.clone())
+        });
+
+        println!(" - loaded {:?} chunks", &self.region_headers.len());
+    }
+}
+
+pub trait RegionWriter {
+    fn save(&mut self, region_path: &str) -> io::Result<()>;
+}
+
+impl RegionWriter for Region {
+    /// Write the region back out as a region file. Edited chunks are encoded again, everything else
+    /// is copied over exactly as it was read, so chunks that weren't touched can't lose anything
+    /// the parser doesn't understand. Chunks are laid out back to back after the header and the
+    /// file is written next to the target first, so a failed save never leaves a half written
+    /// region behind. Once it's written the region counts as unedited again.
+    fn save(&mut self, region_path: &str) -> io::Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0) as u32;
+
+        let mut locations = vec![0u8; 4096];
+        let mut timestamps = vec![0u8; 4096];
+        let mut payloads: Vec<u8> = vec![];
+        let mut offset = 2;
+
+        // chunks that never decoded have nothing worth encoding, their slot is copied like the rest
+        let edited: HashMap<usize, &Chunk> = self.chunks.iter()
+            .filter(|chunk| chunk.modified && 0 != chunk.data_version)
+            .map(|chunk| ((((chunk.z - self.z) >> 4) * 32 + ((chunk.x - self.x) >> 4)) as usize, chunk))
+            .collect();
+
+        for r in 0..1024 {
+            let region_header = self.region_headers.get(&(r as i32));
+
+            let (mut payload, updated) = match (edited.get(&r), region_header) {
+                (Some(chunk), _) => {
+                    let mut payload = chunk.compress();
+
+                    // chunks too big for the region file go in their own c.<x>.<z>.mcc file instead
+                    if 255 * 4096 < payload.len() {
+                        let external_path = Path::new(region_path)
+                            .with_file_name(format!("c.{}.{}.mcc", chunk.x >> 4, chunk.z >> 4));
+                        fs::write(external_path, &payload[5..])?;
+                        payload = vec![0, 0, 0, 1, payload[4] | 128];
+                    }
+
+                    (payload, now)
+                }
+                // slots that are empty on disk stay empty
+                (None, Some(region_header)) if 0 < region_header.size => match self.payload(region_header) {
+                    Some(payload) => (payload.to_vec(), region_header.updated),
+                    None => continue,
+                },
+                _ => continue,
+            };
+
+            let sectors = (payload.len() + 4095) / 4096;
+            payload.resize(sectors * 4096, 0);
+
+            let location = (offset << 8 | sectors as u32).to_be_bytes();
+            locations[r * 4..r * 4 + 4].copy_from_slice(&location);
+            timestamps[r * 4..r * 4 + 4].copy_from_slice(&updated.to_be_bytes());
+
+            payloads.extend(payload);
+            offset += sectors as u32;
+        }
+
+        let temp_path = format!("{}.tmp", region_path);
+        let mut bytes = locations;
+        bytes.extend(timestamps);
+        bytes.extend(payloads);
+        fs::write(&temp_path, &bytes)?;
+        fs::rename(&temp_path, region_path)?;
+
+        // what's on disk now is what later saves copy unedited chunks from
+        self.bytes = bytes;
+        self.parse_headers();
+        for chunk in self.chunks.iter_mut() {
+            chunk.modified = false;
+        }
+
+        Ok(())
+    }
+}
+
+impl Region {
+    /// The region x/z of a region file named like `r.<x>.<z>.mca`, None for anything else that
+    /// ended up in a region folder, e.g. `r.0.0 (copy).mca`
+    pub fn coordinates(region_path: &str) -> Option<(i32, i32)> {
+        let file_name = Path::new(region_path).file_name()?.to_string_lossy().to_string();
+        let parts: Vec<&str> = file_name.split('.').collect();
+        if 4 != parts.len() || "r" != parts[0] { return None }
+
+        Some((parts[1].parse::<i32>().ok()?, parts[2].parse::<i32>().ok()?))
+    }
+
+    /// Work out where each chunk slot is from the header of the file read by load_headers
+    fn parse_headers(&mut self) {
+        self.region_headers.clear();
+
+        // a file too short for its header has no chunks
+        if self.bytes.len() < 8192 { return }
+        let (location_buffer, updated_buffer) = (&self.bytes[..4096], &self.bytes[4096..8192]);
 
         for cur in (0..4096).step_by(4) {
             // get updated timestamp
@@ -109,34 +272,57 @@ impl RegionLoader for Region {
             let chunk_header = RegionHeader { offset, updated, sectors, size, };
             self.region_headers.insert((cur as i32) / 4, chunk_header);
         }
+    }
 
-        // let region = &self.region_headers["3676"];
-        for header in &self.region_headers {
-            let region = header.1;
-            if 0 == region.size { continue; }
-            let mut chunk_buffer = vec![0u8; region.size];
-            match region_file.seek(SeekFrom::Start(region.offset)) {
-                Ok(_) => {}
-                Err(err) => { format!("Failed to find file offset: {:?}", err); () }
-            }
-            match region_file.read_exact(&mut chunk_buffer) {
-                Ok(()) => {},
-                Err(err) => { format!("Failed to read chunk bytes: {:?}", err ); () }
-            }
-        }
+    /// The sectors a chunk slot takes up in the file read by load_headers, empty for slots that
+    /// were never generated
+    fn payload(&self, region_header: &RegionHeader) -> Option<&[u8]> {
+        let start = region_header.offset as usize;
+        self.bytes.get(start..start + region_header.size)
+    }
 
-        let mut r: i32 = 0;
-        for (r, region_header) in &self.region_headers {
-            let offset = u64::from(region_header.offset) ;
-            let _ = region_file.seek(SeekFrom::Start(offset));
-            let mut chunk_buffer = vec![0u8; region_header.size];
-            let _ = region_file.read_exact(&mut chunk_buffer);
-            let x = self.x + (r % 32 * 16);
-            let z = self.z + (r / 32 * 16);
-            let chunk = Chunk::new(chunk_buffer, x, z);
-            self.chunks.push(chunk);
+    /// Iterate over the loaded chunks that reached at least the given generation status
+    pub fn chunks_with_status(&self, status: ChunkStatus) -> impl Iterator<Item = &Chunk> {
+        self.chunks.iter().filter(move |chunk| status <= chunk.status)
+    }
+
+    /// Decode the region's chunks one at a time instead of all at once, skipping empty slots
+    pub fn into_chunks(mut self) -> impl Iterator<Item = Chunk> {
+        if self.region_headers.is_empty() {
+            self.load_headers();
         }
 
-        println!(" - loaded {:?} chunks", &self.region_headers.len());
+        let (registry, dimension_type) = (self.registry.clone(), self.dimension_type.clone());
+        let mut payloads = self.chunk_payloads().into_iter()
+            .filter(|(_, _, chunk_buffer)| 0 < chunk_buffer.len());
+
+        // decoded a few per worker at a time, enough to keep them busy without holding the region
+        let batch_size = threads() * 4;
+        std::iter::from_fn(move || {
+            let batch: Vec<(i32, i32, Vec<u8>)> = payloads.by_ref().take(batch_size).collect();
+            match batch.len() {
+                0 => None,
+                _ => Some(batch),
+            }
+        }).flat_map(move |batch| {
+            let (registry, dimension_type) = (registry.clone(), dimension_type.clone());
+            map_parallel(batch, move |(x, z, chunk_buffer)| {
+                Chunk::new(chunk_buffer, x, z, registry.clone(), dimension_type.clone())
+            })
+        })
+    }
+
+    /// The loaded chunk containing the given block coordinates
+    pub fn chunk_at_mut(&mut self, x: i32, z: i32) -> Option<&mut Chunk> {
+        self.chunks.iter_mut().find(|chunk| chunk.x >> 4 == x >> 4 && chunk.z >> 4 == z >> 4)
+    }
+
+    /// Swap every occurrence of one block state for another across the region
+    pub fn replace_block(&mut self, from: &BlockState, to: BlockState) -> usize {
+        self.chunks.iter_mut().map(|chunk| chunk.replace_block(from, to.clone())).sum()
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.chunks.iter().any(|chunk| chunk.modified)
     }
-}
\ No newline at end of file
+}
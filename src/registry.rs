@@ -0,0 +1,97 @@
+//! BLOCK STATE REGISTRY
+//!
+//! Every section palette names its block states in full, so the same handful of states (stone,
+//! air, dirt, ...) are repeated in nearly every section of the world. The registry keeps one copy
+//! of each unique name and property combination and hands out a compact id for it, so a palette
+//! is just a list of ids and comparing two blocks is comparing two integers.
+//!
+//! | ID -->          |       0       |         1         |                  2                   |
+//! |-----------------|---------------|-------------------|--------------------------------------|
+//! | STATE -->       | minecraft:air | minecraft:stone   | minecraft:oak_log { axis: "y" }      |
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use crate::chunk::BlockState;
+
+pub struct BlockStateRegistry {
+    inner: RwLock<RegistryInner>,
+}
+
+struct RegistryInner {
+    states: Vec<Arc<BlockState>>,
+    ids: HashMap<Arc<BlockState>, u32>,
+}
+
+impl BlockStateRegistry {
+    pub fn new() -> Self {
+        let registry = BlockStateRegistry {
+            inner: RwLock::new(RegistryInner {
+                states: vec![],
+                ids: HashMap::new(),
+            }),
+        };
+
+        // air is always id 0, handy for sections without any block data
+        registry.intern(BlockState::new("minecraft:air"));
+
+        registry
+    }
+
+    /// Get the id of the block state, adding it to the registry if it's new
+    pub fn intern(&self, block_state: BlockState) -> u32 {
+        // most states are already known, so try with the cheaper read lock first
+        if let Some(id) = self.inner.read().unwrap().ids.get(&block_state) {
+            return *id;
+        }
+
+        let mut inner = self.inner.write().unwrap();
+
+        // another thread may have beaten us to it while we waited for the lock
+        if let Some(id) = inner.ids.get(&block_state) {
+            return *id;
+        }
+
+        let id = inner.states.len() as u32;
+        let block_state = Arc::new(block_state);
+        inner.states.push(block_state.clone());
+        inner.ids.insert(block_state, id);
+
+        id
+    }
+
+    /// Look up the block state for an id handed out by this registry
+    pub fn get(&self, id: u32) -> Arc<BlockState> {
+        let inner = self.inner.read().unwrap();
+        match inner.states.get(id as usize) {
+            Some(block_state) => block_state.clone(),
+            None => inner.states[0].clone(),
+        }
+    }
+
+    /// The id of a block state if it has been seen before
+    pub fn id(&self, block_state: &BlockState) -> Option<u32> {
+        self.inner.read().unwrap().ids.get(block_state).copied()
+    }
+
+    /// The ids of every known state of the named block, e.g. all rotations of "minecraft:oak_log"
+    pub fn ids_of(&self, name: &str) -> Vec<u32> {
+        let inner = self.inner.read().unwrap();
+        inner.states.iter().enumerate()
+            .filter(|(_, block_state)| block_state.name == name)
+            .map(|(id, _)| id as u32)
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().states.len()
+    }
+}
+
+impl fmt::Debug for BlockStateRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // printing every state for every chunk holding a reference would be a bit much
+        write!(f, "BlockStateRegistry({} states)", self.len())
+    }
+}
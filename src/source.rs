@@ -0,0 +1,390 @@
+//! WORLD SOURCES
+//!
+//! A world is read either from its directory or straight out of a backup archive, without
+//! extracting anything to disk. Paths handed to a source are relative to the world directory and
+//! always use `/`, e.g. `level.dat` or `DIM-1/region/r.0.0.mca`. Archives usually wrap the world in
+//! a folder of its own, so the folder holding `level.dat` is taken as the world directory. Folders
+//! next to it, like the `world_nether` of a Bukkit server, can be opened from the same archive.
+//!
+//! | SOURCE -->      | directory         | .zip                        | .tar.gz / .tgz                        |
+//! |-----------------|-------------------|-----------------------------|---------------------------------------|
+//! | READS -->       | files as needed   | entries as needed           | entries as needed, streamed from gzip |
+//! | WRITES -->      | yes               | no                          | no                                    |
+//!
+//! Gzip can't seek, so a .tar.gz is indexed in one pass that only notes where each entry starts in
+//! the unpacked stream. Reading an entry carries on streaming from wherever the last read stopped,
+//! and only starts over from the top of the file when it has to go back, so anything reading many
+//! files sorts them with `sort_for_reading` first and reads each of them once.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+
+use flate2::read::GzDecoder;
+use tar::Archive;
+use zip::read::ZipArchive;
+
+/// Anything a region file can be read from
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+pub enum WorldSource {
+    Directory(PathBuf),
+    Zip {
+        archive: Arc<Mutex<ZipArchive<File>>>,
+        /// Entry index and modification time by path within the archive, directories end in `/`
+        entries: Arc<BTreeMap<String, (usize, Option<i64>)>>,
+        /// The world's folder within the archive, empty or ending in `/`
+        root: String,
+    },
+    TarGz {
+        path: PathBuf,
+        /// The unpacked stream and how far into it the last read got
+        stream: Arc<Mutex<(u64, GzDecoder<File>)>>,
+        /// Offset into the unpacked stream, size and modification time by path within the
+        /// archive, directories end in `/`
+        entries: Arc<BTreeMap<String, (u64, u64, Option<i64>)>>,
+        root: String,
+    },
+}
+
+impl std::fmt::Debug for WorldSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WorldSource::Directory(path) => write!(f, "Directory({:?})", path),
+            WorldSource::Zip { entries, root, .. } => write!(f, "Zip({:?} in {:?} entries)", root, entries.len()),
+            WorldSource::TarGz { entries, root, .. } => write!(f, "TarGz({:?} in {:?} entries)", root, entries.len()),
+        }
+    }
+}
+
+impl WorldSource {
+    /// Open a world directory, or a .zip, .tar.gz or .tgz backup of one
+    pub fn open(world_path: &str) -> io::Result<Self> {
+        let path = Path::new(world_path);
+        if path.is_dir() {
+            return Ok(WorldSource::Directory(path.to_path_buf()));
+        }
+
+        let lowercase = world_path.to_lowercase();
+        if lowercase.ends_with(".zip") {
+            WorldSource::open_zip(path)
+        } else if lowercase.ends_with(".tar.gz") || lowercase.ends_with(".tgz") {
+            WorldSource::open_tar_gz(path)
+        } else {
+            Err(io::Error::new(io::ErrorKind::NotFound, format!("{:?} is neither a world directory nor a world backup", world_path)))
+        }
+    }
+
+    /// Paths are used exactly as given, for reading files outside of any world
+    pub fn local() -> Self {
+        WorldSource::Directory(PathBuf::new())
+    }
+
+    fn open_zip(path: &Path) -> io::Result<Self> {
+        let mut archive = ZipArchive::new(File::open(path)?)?;
+
+        let mut names = vec![];
+        for i in 0..archive.len() {
+            let zipfile = archive.by_index(i)?;
+            let modified = zipfile.last_modified();
+            let modified = epoch_seconds(modified.year() as i64, modified.month() as i64, modified.day() as i64)
+                + modified.hour() as i64 * 3600 + modified.minute() as i64 * 60 + modified.second() as i64;
+            names.push((String::from(zipfile.name()), (i, Some(modified))));
+        }
+
+        let (root, entries) = world_folder(names);
+        println!("opened world backup {:?} with {:?} entries", path, entries.len());
+
+        Ok(WorldSource::Zip { archive: Arc::new(Mutex::new(archive)), entries: Arc::new(entries), root })
+    }
+
+    fn open_tar_gz(path: &Path) -> io::Result<Self> {
+        let mut archive = Archive::new(GzDecoder::new(File::open(path)?));
+
+        // only where each entry is gets noted, the tar reader skips over the contents
+        let mut names = vec![];
+        for entry in archive.entries()? {
+            let entry = entry?;
+            let name = entry.path()?.to_string_lossy().to_string();
+
+            // empty directories still count, a dimension's region folder may have nothing in it yet
+            if entry.header().entry_type().is_dir() {
+                names.push((format!("{}/", name.trim_end_matches('/')), (0, 0, None)));
+                continue;
+            }
+            if !entry.header().entry_type().is_file() { continue }
+
+            let modified = entry.header().mtime().ok().map(|mtime| mtime as i64);
+            names.push((name, (entry.raw_file_position(), entry.size(), modified)));
+        }
+
+        let (root, entries) = world_folder(names);
+        println!("indexed world backup {:?} with {:?} entries", path, entries.len());
+
+        let stream = (0, GzDecoder::new(File::open(path)?));
+        Ok(WorldSource::TarGz { path: path.to_path_buf(), stream: Arc::new(Mutex::new(stream)), entries: Arc::new(entries), root })
+    }
+
+    /// Read a .tar.gz entry's bytes, streaming on from the last read or starting over if it's behind
+    fn read_tar_entry(path: &Path, stream: &Mutex<(u64, GzDecoder<File>)>, offset: u64, size: u64) -> io::Result<Vec<u8>> {
+        let mut stream = stream.lock().unwrap();
+        if offset < stream.0 {
+            *stream = (0, GzDecoder::new(File::open(path)?));
+        }
+
+        // if anything below fails the position is unknown, so the next read starts over
+        let skip = offset - stream.0;
+        stream.0 = u64::MAX;
+
+        io::copy(&mut (&mut stream.1).take(skip), &mut io::sink())?;
+        let mut bytes = vec![0u8; size as usize];
+        stream.1.read_exact(&mut bytes)?;
+
+        stream.0 = offset + size;
+        Ok(bytes)
+    }
+
+    pub fn is_archive(&self) -> bool {
+        !matches!(self, WorldSource::Directory(_))
+    }
+
+    /// The name of the world's own folder, e.g. "world"
+    pub fn folder_name(&self) -> Option<String> {
+        match self {
+            WorldSource::Directory(root) => {
+                let root = root.canonicalize().ok()?;
+                Some(root.file_name()?.to_string_lossy().to_string())
+            }
+            WorldSource::Zip { root, .. } | WorldSource::TarGz { root, .. } => {
+                let folder = root.trim_end_matches('/').rsplit('/').next()?;
+                match folder {
+                    "" => None,
+                    folder => Some(String::from(folder)),
+                }
+            }
+        }
+    }
+
+    /// Open the folder next to the world's own that's named after it with the given suffix, e.g.
+    /// "world_nether" for "_nether", if there is one
+    pub fn sibling(&self, suffix: &str) -> Option<WorldSource> {
+        let sibling_name = format!("{}{}", self.folder_name()?, suffix);
+
+        let sibling = match self {
+            WorldSource::Directory(root) => {
+                let sibling_path = root.canonicalize().ok()?.parent()?.join(&sibling_name);
+                WorldSource::Directory(sibling_path)
+            }
+            WorldSource::Zip { archive, entries, root } => WorldSource::Zip {
+                archive: archive.clone(),
+                entries: entries.clone(),
+                root: sibling_root(root, &sibling_name),
+            },
+            WorldSource::TarGz { path, stream, entries, root } => WorldSource::TarGz {
+                path: path.clone(),
+                stream: stream.clone(),
+                entries: entries.clone(),
+                root: sibling_root(root, &sibling_name),
+            },
+        };
+
+        match sibling.is_dir("") {
+            true => Some(sibling),
+            false => None,
+        }
+    }
+
+    /// Where a file lives on disk, archives have nowhere to write back to
+    pub fn local_path(&self, relative: &str) -> Option<PathBuf> {
+        match self {
+            WorldSource::Directory(root) => Some(root.join(relative)),
+            _ => None,
+        }
+    }
+
+    pub fn is_file(&self, relative: &str) -> bool {
+        match self {
+            WorldSource::Directory(root) => root.join(relative).is_file(),
+            WorldSource::Zip { entries, root, .. } => entries.contains_key(&format!("{}{}", root, relative)),
+            WorldSource::TarGz { entries, root, .. } => entries.contains_key(&format!("{}{}", root, relative)),
+        }
+    }
+
+    pub fn is_dir(&self, relative: &str) -> bool {
+        match self {
+            WorldSource::Directory(root) => root.join(relative).is_dir(),
+            _ => {
+                let prefix = self.prefix(relative);
+                self.paths().any(|path| path.starts_with(&prefix))
+            }
+        }
+    }
+
+    /// The names of the files and directories directly inside a directory, sorted
+    pub fn list(&self, relative: &str) -> Vec<String> {
+        let mut names: Vec<String> = match self {
+            WorldSource::Directory(root) => match fs::read_dir(root.join(relative)) {
+                Ok(results) => results
+                    .filter_map(|result| result.ok())
+                    .map(|entry| entry.file_name().to_string_lossy().to_string())
+                    .collect(),
+                Err(_) => vec![],
+            },
+            _ => {
+                let prefix = self.prefix(relative);
+                self.paths()
+                    .filter_map(|path| path.strip_prefix(&prefix))
+                    .map(|rest| String::from(rest.split('/').next().unwrap()))
+                    .filter(|name| 0 < name.len())
+                    .collect()
+            }
+        };
+
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    pub fn read(&self, relative: &str) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![];
+        self.open_file(relative)?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Open a file for reading. Compressed archive entries can't seek, so they're read into memory.
+    pub fn open_file(&self, relative: &str) -> io::Result<Box<dyn ReadSeek + '_>> {
+        match self {
+            WorldSource::Directory(root) => Ok(Box::new(File::open(root.join(relative))?)),
+            WorldSource::Zip { archive, entries, root } => {
+                let (index, _) = entries.get(&format!("{}{}", root, relative)).ok_or(not_found(relative))?;
+                let mut archive = archive.lock().unwrap();
+                let mut zipfile = archive.by_index(*index)?;
+                let mut bytes = Vec::with_capacity(zipfile.size() as usize);
+                zipfile.read_to_end(&mut bytes)?;
+                Ok(Box::new(Cursor::new(bytes)))
+            }
+            WorldSource::TarGz { path, stream, entries, root } => {
+                let (offset, size, _) = entries.get(&format!("{}{}", root, relative)).ok_or(not_found(relative))?;
+                let bytes = WorldSource::read_tar_entry(path, stream, *offset, *size)?;
+                Ok(Box::new(Cursor::new(bytes)))
+            }
+        }
+    }
+
+    /// Sort paths into the order they're cheapest to read in. A .tar.gz is read in the order its
+    /// entries are stored, so reading them all streams through the archive once.
+    pub fn sort_for_reading(&self, paths: &mut [String]) {
+        if let WorldSource::TarGz { entries, root, .. } = self {
+            paths.sort_by_key(|relative| entries.get(&format!("{}{}", root, relative)).map(|(offset, _, _)| *offset));
+        }
+    }
+
+    /// When a file was last written, in epoch seconds
+    pub fn modified(&self, relative: &str) -> Option<i64> {
+        match self {
+            WorldSource::Directory(root) => {
+                let modified = fs::metadata(root.join(relative)).and_then(|meta| meta.modified()).ok()?;
+                Some(modified.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64)
+            }
+            WorldSource::Zip { entries, root, .. } => entries.get(&format!("{}{}", root, relative))?.1,
+            WorldSource::TarGz { entries, root, .. } => entries.get(&format!("{}{}", root, relative))?.2,
+        }
+    }
+
+    /// Every path in the archive, including those outside of the world's folder
+    fn paths(&self) -> Box<dyn Iterator<Item = &String> + '_> {
+        match self {
+            WorldSource::Directory(_) => Box::new(std::iter::empty()),
+            WorldSource::Zip { entries, .. } => Box::new(entries.keys()),
+            WorldSource::TarGz { entries, .. } => Box::new(entries.keys()),
+        }
+    }
+
+    /// What every archive path within a directory of the world starts with
+    fn prefix(&self, relative: &str) -> String {
+        let root = match self {
+            WorldSource::Directory(_) => "",
+            WorldSource::Zip { root, .. } | WorldSource::TarGz { root, .. } => root.as_str(),
+        };
+
+        match relative.trim_end_matches('/') {
+            "" => String::from(root),
+            relative => format!("{}{}/", root, relative),
+        }
+    }
+}
+
+/// Join a directory and a name the way paths within a source are written
+pub fn join(directory: &str, name: &str) -> String {
+    match directory.trim_end_matches('/') {
+        "" => String::from(name),
+        directory => format!("{}/{}", directory, name),
+    }
+}
+
+fn not_found(relative: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{:?} isn't in the world backup", relative))
+}
+
+/// Tidy up archive paths and pick the world's folder, which is wherever the shallowest `level.dat`
+/// is, or failing that the shallowest `region` directory. Bukkit servers keep a level.dat in their
+/// `_nether` and `_the_end` folders too, so those lose out to the main world.
+fn world_folder<T>(entries: Vec<(String, T)>) -> (String, BTreeMap<String, T>) {
+    let entries: BTreeMap<String, T> = entries.into_iter()
+        .map(|(name, value)| (name.trim_start_matches("./").replace('\\', "/"), value))
+        .collect();
+
+    let shallowest = |marker: &str| entries.keys()
+        .filter_map(|name| folder_of(name, marker))
+        .min_by_key(|folder| (folder.matches('/').count(), folder.ends_with("_nether/") || folder.ends_with("_the_end/")));
+
+    let root = shallowest("/level.dat")
+        .or_else(|| shallowest("/region/"))
+        .unwrap_or_default();
+
+    (root, entries)
+}
+
+/// The root of a folder next to the given one within an archive
+fn sibling_root(root: &str, sibling_name: &str) -> String {
+    match root.trim_end_matches('/').rsplit_once('/') {
+        Some((parent, _)) => format!("{}/{}/", parent, sibling_name),
+        None => format!("{}/", sibling_name),
+    }
+}
+
+/// The folder an archive path sits in, with a trailing slash, if the path ends in the given file
+/// name or passes through the given directory
+fn folder_of(name: &str, marker: &str) -> Option<String> {
+    let name = format!("/{}", name);
+    let end = match marker.ends_with('/') {
+        true => name.find(marker)?,
+        false => match name.ends_with(marker) {
+            true => name.len() - marker.len(),
+            false => return None,
+        },
+    };
+
+    match name[..end].trim_start_matches('/') {
+        "" => Some(String::new()),
+        folder => Some(format!("{}/", folder)),
+    }
+}
+
+/// Seconds since the epoch at midnight of a date, zip entries only store local calendar dates
+fn epoch_seconds(year: i64, month: i64, day: i64) -> i64 {
+    // days from civil, counting from march so the leap day comes last
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    (era * 146097 + day_of_era - 719468) * 86400
+}
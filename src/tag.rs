@@ -302,6 +302,158 @@ impl Tag {
         }
     }
 
+    fn type_id(tag_type: TagType) -> u8 {
+        // return id from tag type
+        match tag_type {
+            TagType::End => 0,
+            TagType::Byte => 1,
+            TagType::Short => 2,
+            TagType::Int => 3,
+            TagType::Long => 4,
+            TagType::Float => 5,
+            TagType::Double => 6,
+            TagType::ByteArray => 7,
+            TagType::String => 8,
+            TagType::List => 9,
+            TagType::Compound => 10,
+            TagType::IntArray => 11,
+            TagType::LongArray => 12,
+            TagType::Invalid => 99,
+        }
+    }
+
+    fn header(tag_type: TagType, name: &str) -> Vec<u8> {
+        // id byte, then the name length and the name itself
+        let mut bytes = vec![Tag::type_id(tag_type)];
+        bytes.extend((name.len() as u16).to_be_bytes());
+        bytes.extend(name.as_bytes());
+        bytes
+    }
+
+    fn from_payload(tag_type: TagType, name: &str, payload: &[u8]) -> Self {
+        let mut bytes = Tag::header(tag_type, name);
+        bytes.extend(payload);
+
+        Tag {
+            name: String::from(name),
+            tagtype: tag_type,
+            bytes,
+            subtags: vec![],
+        }
+    }
+
+    pub fn byte(name: &str, value: i8) -> Self {
+        Tag::from_payload(TagType::Byte, name, &value.to_be_bytes())
+    }
+    pub fn short(name: &str, value: i16) -> Self {
+        Tag::from_payload(TagType::Short, name, &value.to_be_bytes())
+    }
+    pub fn int(name: &str, value: i32) -> Self {
+        Tag::from_payload(TagType::Int, name, &value.to_be_bytes())
+    }
+    pub fn long(name: &str, value: i64) -> Self {
+        Tag::from_payload(TagType::Long, name, &value.to_be_bytes())
+    }
+    pub fn float(name: &str, value: f32) -> Self {
+        Tag::from_payload(TagType::Float, name, &value.to_be_bytes())
+    }
+    pub fn double(name: &str, value: f64) -> Self {
+        Tag::from_payload(TagType::Double, name, &value.to_be_bytes())
+    }
+    pub fn string(name: &str, value: &str) -> Self {
+        let mut payload = (value.len() as u16).to_be_bytes().to_vec();
+        payload.extend(value.as_bytes());
+        Tag::from_payload(TagType::String, name, &payload)
+    }
+    pub fn byte_array(name: &str, values: &[u8]) -> Self {
+        let mut payload = (values.len() as i32).to_be_bytes().to_vec();
+        payload.extend(values);
+        Tag::from_payload(TagType::ByteArray, name, &payload)
+    }
+    pub fn int_array(name: &str, values: &[i32]) -> Self {
+        let mut payload = (values.len() as i32).to_be_bytes().to_vec();
+        for value in values { payload.extend(value.to_be_bytes()) }
+        Tag::from_payload(TagType::IntArray, name, &payload)
+    }
+    pub fn long_array(name: &str, values: &[i64]) -> Self {
+        let mut payload = (values.len() as i32).to_be_bytes().to_vec();
+        for value in values { payload.extend(value.to_be_bytes()) }
+        Tag::from_payload(TagType::LongArray, name, &payload)
+    }
+
+    /// Create a list tag, the elements are stored without names just like the parser reads them
+    pub fn list(name: &str, element_type: TagType, elements: Vec<Tag>) -> Self {
+        let mut payload = vec![Tag::type_id(element_type)];
+        payload.extend((elements.len() as i32).to_be_bytes());
+
+        let mut subtags = vec![];
+        for element in elements {
+            let element_payload = &element.bytes[3 + element.name.len()..];
+            payload.extend(element_payload);
+
+            let mut subtag = Tag::from_payload(element.tagtype, "", element_payload);
+            subtag.subtags = element.subtags;
+            subtags.push(subtag);
+        }
+
+        let mut tag = Tag::from_payload(TagType::List, name, &payload);
+        tag.subtags = subtags;
+        tag
+    }
+
+    /// Create an empty compound tag, ready to have other tags pushed into it
+    pub fn compound(name: &str) -> Self {
+        Tag::compound_with(name, vec![])
+    }
+
+    /// Create a compound tag holding the given tags
+    pub fn compound_with(name: &str, subtags: Vec<Tag>) -> Self {
+        let mut tag = Tag {
+            name: String::from(name),
+            tagtype: TagType::Compound,
+            bytes: vec![],
+            subtags,
+        };
+        tag.subtags.retain(|subtag| TagType::End != subtag.tagtype);
+        tag.subtags.push(Tag::new(vec![0]));
+        tag.rebuild();
+        tag
+    }
+
+    /// Add a tag to a compound, keeping the End tag last and the raw bytes in sync
+    pub fn push(&mut self, subtag: Tag) {
+        if TagType::Compound != self.tagtype { return }
+
+        let end = self.subtags.len().saturating_sub(1);
+        self.subtags.insert(end, subtag);
+        self.rebuild();
+    }
+
+    /// Find a tag in a compound by name
+    pub fn get(&self, name: &str) -> Option<&Tag> {
+        self.subtags.iter().find(|subtag| subtag.name == name && TagType::End != subtag.tagtype)
+    }
+
+    /// Take a tag out of a compound by name
+    pub fn remove(&mut self, name: &str) -> Option<Tag> {
+        if TagType::Compound != self.tagtype { return None }
+
+        let index = self.subtags.iter()
+            .position(|subtag| subtag.name == name && TagType::End != subtag.tagtype)?;
+        let removed = self.subtags.remove(index);
+        self.rebuild();
+
+        Some(removed)
+    }
+
+    fn rebuild(&mut self) {
+        // a compound's bytes are just its header followed by the bytes of everything inside it
+        self.bytes = Tag::header(TagType::Compound, &self.name);
+        for subtag in &self.subtags {
+            self.bytes.extend(&subtag.bytes);
+        }
+    }
+
     pub fn payload_byte(&self) -> i8 {
         let bytes: Vec<u8> = self.bytes.iter().rev().cloned().collect();
         i8::from_be_bytes([bytes[0]])
@@ -341,6 +493,23 @@ impl Tag {
         let cursor = 5 + i16::from_be_bytes([self.bytes[1], self.bytes[2]]) as usize;
         bytes_to_utf8(self.bytes[cursor..].to_vec())
     }
+    /// The plain text of a text component, which is a compound with `text` and `extra` or a list of
+    /// components since 1.21.5. Before that it's a string of json, which is returned as it is.
+    /// None for any other kind of tag.
+    pub fn payload_text(&self) -> Option<String> {
+        match self.tagtype {
+            TagType::String => Some(self.payload_string()),
+            TagType::Compound => {
+                let mut text = self.get("text").and_then(Tag::payload_text).unwrap_or_default();
+                if let Some(extra) = self.get("extra") {
+                    text.push_str(&extra.payload_text().unwrap_or_default());
+                }
+                Some(text)
+            }
+            TagType::List => Some(self.subtags.iter().filter_map(Tag::payload_text).collect()),
+            _ => None,
+        }
+    }
     pub fn payload_int_array(&self) -> Vec<i32> {
         let cursor = 7 + i16::from_be_bytes([self.bytes[1], self.bytes[2]]) as usize;
         let mut output = vec![];
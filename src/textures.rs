@@ -1,16 +1,50 @@
-use std::collections::HashMap;
+//! RESOURCE PACKS
+//!
+//! Every zipped pack is extracted once into a cache directory of its own, keeping the layout it has
+//! inside the zip, while unzipped packs are read in place so edits show up on the next render. A
+//! render stacks packs the way the client does: each asset comes from the topmost pack that has
+//! it, and the vanilla jar at the bottom fills in everything else.
+//!
+//! | STACK -->       | textures/block/stone.png | blockstates/stone.json | textures/colormap/grass.png |
+//! |-----------------|--------------------------|------------------------|-----------------------------|
+//! | my_overrides -> | supplied                 |                        |                             |
+//! | faithful -->    | overridden               | supplied               |                             |
+//! | vanilla jar --> | overridden               | overridden             | supplied                    |
+//!
+//! A pack's `pack.mcmeta` says which pack format it was made for, and since 1.20.2 the range of
+//! formats it also works with:
+//!
+//! | KEY -->         | pack_format | supported_formats                                          | description                |
+//! |-----------------|-------------|------------------------------------------------------------|----------------------------|
+//! | VALUE -->       | 15          | 18, [15, 22] or {"min_inclusive": 15, "max_inclusive": 22} | "text" or a text component |
+//!
+//! Since 1.20.2 a pack can also list `overlays`, folders next to its `assets` with assets of their
+//! own, each applied on top of the pack for a range of formats. Later overlays win over earlier ones.
+
+use std::collections::{BTreeMap, HashMap};
 use std::{env, fs};
 use std::fs::File;
 use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use sha1;
 use sha1::{Sha1, Digest};
 use regex::Regex;
 use jars::JarOptionBuilder;
 use zip::read::ZipArchive;
+use serde_json::Value;
+use crate::version::MinecraftVersion;
 use crate::world::{DeepDirectoryDriver, World};
 
+/// Everything a render needs from a pack, textures (colormaps included), blockstates and models,
+/// from the pack itself or one of its overlay folders
+const ASSET_PATTERN: &str = r"^((?:[^/\\]+.)?assets.minecraft.(?:textures|blockstates|models).+\.(?:png|json|mcmeta))$";
+
+/// Bumped whenever what gets extracted or where it lands changes, so caches from older builds
+/// are extracted again instead of being read with the wrong layout
+const CACHE_LAYOUT: &str = "v3";
+
 pub struct TexturePack {
     pub filepath: String,
     pub block_table: HashMap<String, String>,
@@ -27,36 +61,68 @@ impl TexturePack {
         let mut output: HashMap<String, PathBuf> = Default::default();
 
         for (name, path) in textures {
-            // get file hash of texture path for cache
-            let hash = TexturePack::file_hash(&path);
+            output.insert(name, TexturePack::extract(&path));
+        }
 
-            // create path buffer for cache directory
-            let mut path_buf = env::current_dir().unwrap();
-            for subdir in ["cache", "textures", &hash] { path_buf.push(subdir) }
+        output
+    }
 
-            // add to output
-            output.insert(name, path_buf.clone());
+    /// Extract a pack's assets into the cache unless that's already been done, returning the
+    /// folder holding its `assets`. Unzipped packs don't need extracting and are used where they are.
+    pub fn extract(path: &str) -> PathBuf {
+        if Path::new(path).is_dir() {
+            return PathBuf::from(path);
+        }
 
-            // skip if it exists
-            if path_buf.exists() {
-                continue
-            }
+        // get file hash of texture path for cache
+        let hash = TexturePack::file_hash(path);
+
+        // create path buffer for cache directory
+        let mut path_buf = env::current_dir().unwrap();
+        for subdir in ["cache", "resourcepacks", CACHE_LAYOUT, &hash] { path_buf.push(subdir) }
+
+        // skip if it exists
+        if path_buf.exists() {
+            return path_buf
+        }
+
+        // create it if not exists
+        match fs::create_dir_all(&path_buf) {
+            Ok(_) => {}, // no news is good news
+            Err(err) => eprintln!("Error creating cache: {}", err),
+        }
 
-            // create it if not exists
-            match fs::create_dir_all(&path_buf) {
-                Ok(_) => {}, // no news is good news
-                Err(err) => eprintln!("Error creating cache: {}", err),
+        // extract texture files
+        let extension = Path::new(path).extension().map(|extension| extension.to_string_lossy().to_lowercase());
+        let path = String::from(path);
+        match extension.as_deref() {
+            Some("jar") => TexturePack::extract_jar_textures(&path, &path_buf),
+            Some("zip") => TexturePack::extract_zip_textures(&path, &path_buf),
+            _ => println!("unsupported texture container: {:?}", &path),
+        }
+
+        path_buf
+    }
+
+    /// Read a pack's pack.mcmeta, from its folder or from inside the zip or jar
+    pub fn read_meta(path: &str) -> Option<PackMeta> {
+        let content = match Path::new(path).is_dir() {
+            true => fs::read_to_string(Path::new(path).join("pack.mcmeta")).ok()?,
+            false => {
+                let mut archive = ZipArchive::new(File::open(path).ok()?).ok()?;
+                let mut content = String::new();
+                archive.by_name("pack.mcmeta").ok()?.read_to_string(&mut content).ok()?;
+                content
             }
+        };
 
-            // extract texture files
-            match &path[path.len() - 3..] {
-                "jar" => TexturePack::extract_jar_textures(&path, &path_buf),
-                "zip" => TexturePack::extract_zip_textures(&path, &path_buf),
-                _ => println!("unsupported texture container: {:?}", &path),
+        match PackMeta::parse(&content) {
+            Some(meta) => Some(meta),
+            None => {
+                log::warn!("couldn't read pack.mcmeta of {:?}", path);
+                None
             }
         }
-
-        output
     }
 
     fn file_hash(file_path: &str) -> String {
@@ -74,7 +140,7 @@ impl TexturePack {
 
     fn extract_jar_textures(path: &String, path_buf: &PathBuf) {
         // file pattern
-        let pattern = Regex::new(r"^assets.minecraft.textures.(.+\.png)$").expect("the unexpected");
+        let pattern = Regex::new(ASSET_PATTERN).expect("the unexpected");
 
         let mut bytes_written = 0;
 
@@ -93,8 +159,13 @@ impl TexturePack {
             if pattern.is_match(&file) {
                 // create cache path variable
                 let mut cache_path = std::path::PathBuf::from(&path_buf);
-                let subdirs: Vec<&str> = pattern.captures(&file).unwrap()
-                    .get(1).unwrap().as_str().split("/").collect();
+                let subdirs = match cache_segments(pattern.captures(&file).unwrap().get(1).unwrap().as_str()) {
+                    Some(subdirs) => subdirs,
+                    None => {
+                        log::warn!("skipping {:?} in {:?}, it would be written outside the cache", file, path);
+                        continue
+                    }
+                };
                 for subdir in subdirs { cache_path.push(subdir) }
 
                 // skip if it exists
@@ -125,7 +196,7 @@ impl TexturePack {
 
     fn extract_zip_textures(path: &String, path_buf: &PathBuf) {
         // file pattern
-        let pattern = Regex::new(r"^assets.minecraft.textures.(.+\.png)$").expect("the unexpected");
+        let pattern = Regex::new(ASSET_PATTERN).expect("the unexpected");
 
         let mut bytes_written = 0;
 
@@ -170,8 +241,13 @@ impl TexturePack {
 
             // build full path string from current path buffer
             let mut zip_path = std::path::PathBuf::from(&path_buf);
-            let subdirs: Vec<&str> = pattern.captures(&filename).unwrap()
-                .get(1).unwrap().as_str().split("/").collect();
+            let subdirs = match cache_segments(pattern.captures(&filename).unwrap().get(1).unwrap().as_str()) {
+                Some(subdirs) => subdirs,
+                None => {
+                    log::warn!("skipping {:?} in {:?}, it would be written outside the cache", filename, path);
+                    continue
+                }
+            };
             for subdir in subdirs { zip_path.push(subdir) }
 
             // create parent folders if not in existance
@@ -214,4 +290,259 @@ impl TexturePack {
         // good job, team! we did it!
         println!("{:?} bytes successfully written", &bytes_written);
     }
-}
\ No newline at end of file
+}
+
+/// What a pack's pack.mcmeta says about it
+#[derive(Debug, Clone)]
+pub struct PackMeta {
+    pub pack_format: u32,
+    /// Formats the pack works with besides its own, when it says so
+    pub supported_formats: Option<RangeInclusive<u32>>,
+    pub description: String,
+    pub overlays: Vec<Overlay>,
+}
+
+/// A folder of assets applied on top of its pack when the game reads one of its formats
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    pub formats: RangeInclusive<u32>,
+    pub directory: String,
+}
+
+impl PackMeta {
+    pub fn parse(content: &str) -> Option<Self> {
+        let json: Value = serde_json::from_str(content).ok()?;
+        let pack = &json["pack"];
+
+        // 1.21.9 replaced pack_format with min_format and max_format
+        let pack_format = major_format(&pack["pack_format"]).or_else(|| major_format(&pack["max_format"]))?;
+        let supported_formats = format_range(pack, "supported_formats");
+
+        let overlays = json["overlays"]["entries"].as_array().into_iter().flatten()
+            .filter_map(|entry| Some(Overlay {
+                formats: format_range(entry, "formats")?,
+                directory: String::from(entry["directory"].as_str()?),
+            }))
+            .filter(|overlay| match valid_overlay_directory(&overlay.directory) {
+                true => true,
+                false => {
+                    log::warn!("skipping overlay with invalid directory {:?}", overlay.directory);
+                    false
+                }
+            })
+            .collect();
+
+        Some(PackMeta { pack_format, supported_formats, description: text_of(&pack["description"]), overlays })
+    }
+
+    /// Whether the pack works with any of the given formats
+    pub fn supports(&self, formats: &RangeInclusive<u32>) -> bool {
+        match &self.supported_formats {
+            _ if formats.contains(&self.pack_format) => true,
+            Some(supported) => supported.start().max(formats.start()) <= supported.end().min(formats.end()),
+            None => false,
+        }
+    }
+}
+
+/// A format as a number, or since 1.21.9 as [major, minor] of which only the major counts
+fn major_format(value: &Value) -> Option<u32> {
+    value.as_u64().or_else(|| value[0].as_u64()).map(|format| format as u32)
+}
+
+/// The formats under `key`, as a single format, [min, max] or {"min_inclusive", "max_inclusive"}.
+/// Since 1.21.9 they're `min_format` and `max_format` next to it instead.
+fn format_range(value: &Value, key: &str) -> Option<RangeInclusive<u32>> {
+    if let (Some(min), Some(max)) = (major_format(&value["min_format"]), major_format(&value["max_format"])) {
+        return Some(min..=max);
+    }
+
+    let formats = &value[key];
+    match (formats.as_u64(), formats.as_array()) {
+        (Some(format), _) => Some(format as u32..=format as u32),
+        (None, Some(range)) if 2 == range.len() => Some(major_format(&range[0])?..=major_format(&range[1])?),
+        _ => Some(major_format(&formats["min_inclusive"])?..=major_format(&formats["max_inclusive"])?),
+    }
+}
+
+/// The folders and file name an archive entry is extracted to within the cache, None if any of
+/// them is empty or would step out of it, like the `..` in `assets/minecraft/textures/../../x.png`
+fn cache_segments(entry: &str) -> Option<Vec<&str>> {
+    let segments: Vec<&str> = entry.split(['/', '\\']).collect();
+    match segments.iter().any(|segment| ["", ".", ".."].contains(segment)) {
+        true => None,
+        false => Some(segments),
+    }
+}
+
+/// Whether an overlay directory is a single folder name of `[a-z0-9_.-]`, anything else could point
+/// outside the pack
+fn valid_overlay_directory(directory: &str) -> bool {
+    !directory.is_empty()
+        && directory.chars().any(|char| '.' != char)
+        && directory.chars().all(|char| matches!(char, 'a'..='z' | '0'..='9' | '_' | '.' | '-'))
+}
+
+/// The plain text of a description, which can also be a text component or a list of them
+fn text_of(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts.iter().map(text_of).collect(),
+        Value::Object(component) => {
+            let mut text = component.get("text").map(text_of).unwrap_or_default();
+            if let Some(extra) = component.get("extra") {
+                text.push_str(&text_of(extra));
+            }
+            text
+        }
+        _ => String::new(),
+    }
+}
+
+#[derive(Debug)]
+pub struct ResourcePack {
+    /// The zip, jar or folder the pack was loaded from
+    pub path: String,
+    /// Where its assets/minecraft files are
+    pub directory: PathBuf,
+    /// Overlays applied for the render's version and where their assets/minecraft files are, the
+    /// one applied last first
+    pub overlays: Vec<(String, PathBuf)>,
+    pub meta: Option<PackMeta>,
+}
+
+impl ResourcePack {
+    /// The overlay supplying an asset, None for the pack itself, and where the file is
+    fn locate(&self, asset: &str) -> Option<(Option<&str>, PathBuf)> {
+        for (overlay, directory) in &self.overlays {
+            if directory.join(asset).is_file() {
+                return Some((Some(overlay.as_str()), directory.join(asset)));
+            }
+        }
+
+        match self.directory.join(asset).is_file() {
+            true => Some((None, self.directory.join(asset))),
+            false => None,
+        }
+    }
+}
+
+/// Resource packs layered for one render, the first pack wins
+#[derive(Debug, Default)]
+pub struct PackStack {
+    /// Top of the stack first
+    pub packs: Vec<ResourcePack>,
+    /// Every pack with the asset, top first, by path within assets/minecraft
+    pub assets: BTreeMap<String, Vec<usize>>,
+}
+
+impl PackStack {
+    /// Extract and index each pack, `packs` goes from the top of the stack down to the vanilla jar.
+    /// Packs made for a different pack format than `target` reads are still used, with a warning,
+    /// and only overlays for a format `target` reads are applied.
+    pub fn new(packs: &Vec<String>, target: Option<&MinecraftVersion>) -> Self {
+        let mut stack = PackStack::default();
+        let pattern = Regex::new(ASSET_PATTERN).expect("the unexpected");
+        let formats = target.and_then(|target| target.pack_formats());
+
+        for (index, path) in packs.iter().enumerate() {
+            let root = TexturePack::extract(path);
+            let directory = root.join("assets").join("minecraft");
+            let meta = TexturePack::read_meta(path);
+
+            match (&meta, &formats, target) {
+                (Some(meta), Some(formats), Some(target)) if !meta.supports(formats) => {
+                    log::warn!("resource pack {:?} ({}) is made for pack format {}, but {} reads {:?}",
+                        path, meta.description, meta.pack_format, target, formats);
+                }
+                // the vanilla jar is the only pack that doesn't need one
+                (None, _, _) if !path.to_lowercase().ends_with(".jar") => {
+                    log::warn!("resource pack {:?} has no pack.mcmeta", path);
+                }
+                _ => {}
+            }
+
+            // later overlays go on top of earlier ones
+            let mut overlays = vec![];
+            if let (Some(meta), Some(formats)) = (&meta, &formats) {
+                for overlay in meta.overlays.iter().rev() {
+                    if overlay.formats.start().max(formats.start()) <= overlay.formats.end().min(formats.end()) {
+                        let overlay_directory = root.join(&overlay.directory).join("assets").join("minecraft");
+                        overlays.push((overlay.directory.clone(), overlay_directory));
+                    }
+                }
+            }
+
+            // unzipped packs can hold anything, only take what would've been extracted
+            let mut files = vec![];
+            PackStack::list_files(&directory, "", &mut files);
+            for (_, overlay_directory) in &overlays {
+                PackStack::list_files(overlay_directory, "", &mut files);
+            }
+            files.sort();
+            files.dedup();
+            for file in files {
+                if pattern.is_match(&format!("assets/minecraft/{}", file)) {
+                    stack.assets.entry(file).or_default().push(index);
+                }
+            }
+
+            stack.packs.push(ResourcePack { path: path.clone(), directory, overlays, meta });
+        }
+
+        stack
+    }
+
+    fn list_files(directory: &Path, prefix: &str, files: &mut Vec<String>) {
+        for entry in fs::read_dir(directory).into_iter().flatten().flatten() {
+            let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+            match entry.path().is_dir() {
+                true => PackStack::list_files(&entry.path(), &format!("{}/", name), files),
+                false => files.push(name),
+            }
+        }
+    }
+
+    /// The cached file for an asset, e.g. "textures/block/stone.png", from the topmost pack with it
+    pub fn resolve(&self, asset: &str) -> Option<PathBuf> {
+        let index = *self.assets.get(asset)?.first()?;
+        Some(self.packs[index].locate(asset)?.1)
+    }
+
+    /// The pack supplying an asset
+    pub fn provider(&self, asset: &str) -> Option<&str> {
+        let index = *self.assets.get(asset)?.first()?;
+        Some(self.packs[index].path.as_str())
+    }
+
+    /// One row per asset with the pack supplying it, the overlay if one did, and the packs further
+    /// down it overrides
+    pub fn provenance(&self) -> String {
+        let mut csv = String::from("asset,pack,overlay,overrides\n");
+
+        for (asset, indexes) in &self.assets {
+            let pack = &self.packs[indexes[0]];
+            let overlay = pack.locate(asset).and_then(|(overlay, _)| overlay).unwrap_or("");
+            let overridden: Vec<&str> = indexes[1..].iter().map(|&index| self.packs[index].path.as_str()).collect();
+            csv.push_str(&format!("{},{},{},{}\n",
+                csv_field(asset), csv_field(&pack.path), csv_field(overlay), csv_field(&overridden.join(";"))));
+        }
+
+        csv
+    }
+
+    pub fn write_report(&self, output_path: &Path) {
+        match fs::write(output_path, self.provenance()) {
+            Ok(()) => println!("wrote pack report for {:?} assets to {:?}", self.assets.len(), output_path),
+            Err(err) => eprintln!("Error writing pack report ({:?}): {err}", output_path),
+        }
+    }
+}
+
+/// Quote a CSV field if it needs it, pack paths can hold commas
+fn csv_field(value: &str) -> String {
+    match value.contains(',') || value.contains('"') {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => String::from(value),
+    }
+}
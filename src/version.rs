@@ -0,0 +1,293 @@
+//! MINECRAFT VERSIONS
+//!
+//! Version ids come in a handful of shapes. Releases are `1.20.3`, pre-releases and release
+//! candidates hang off the release they lead up to, and weekly snapshots are named after the year
+//! and week they came out in. Before 1.0 every development phase had a prefix of its own.
+//!
+//! | KIND -->        | release | pre-release                        | release candidate | snapshot                | experimental                 |
+//! |-----------------|---------|------------------------------------|-------------------|-------------------------|------------------------------|
+//! | ID -->          | 1.20.3  | 1.20.3-pre2, 1.14 Pre-Release 2    | 1.20.3-rc1        | 23w45a, 26.1-snapshot-1 | 1.18_experimental-snapshot-1 |
+//!
+//! | PHASE -->       | pre-classic | classic   | indev       | infdev       | alpha  | beta   |
+//! |-----------------|-------------|-----------|-------------|--------------|--------|--------|
+//! | ID -->          | rd-132211   | c0.30_01c | in-20100223 | inf-20100618 | a1.2.6 | b1.7.3 |
+
+//! DATA VERSIONS
+//!
+//! Every version since 15w32a stamps the data it saves with a DataVersion number, which only ever
+//! goes up. Releases each have exactly one. Snapshots, pre-releases and release candidates fall
+//! somewhere between the release before them and the one they lead up to, and which release a
+//! snapshot leads up to is worked out from the week it came out in.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+/// Release id, DataVersion (0 before 15w32a) and the last snapshot week leading up to it as yyww.
+/// Patch releases without snapshots of their own stay before the next release's first snapshot week.
+const RELEASES: &[(&str, i32, u32)] = &[
+    ("1.0", 0, 1146), ("1.1", 0, 1202), ("1.2.1", 0, 1209), ("1.3.1", 0, 1231), ("1.4.2", 0, 1243),
+    ("1.4.6", 0, 1251), ("1.5", 0, 1310), ("1.5.1", 0, 1312), ("1.6.1", 0, 1326), ("1.7.2", 0, 1343),
+    ("1.7.4", 0, 1349), ("1.8", 0, 1436), ("1.9", 169, 1609), ("1.9.1", 175, 1612), ("1.9.2", 176, 1613),
+    ("1.9.3", 183, 1619), ("1.9.4", 184, 1619), ("1.10", 510, 1623), ("1.10.1", 511, 1625), ("1.10.2", 512, 1625),
+    ("1.11", 819, 1646), ("1.11.1", 921, 1651), ("1.11.2", 922, 1651), ("1.12", 1139, 1723), ("1.12.1", 1241, 1731),
+    ("1.12.2", 1343, 1738), ("1.13", 1519, 1829), ("1.13.1", 1628, 1834), ("1.13.2", 1631, 1842), ("1.14", 1952, 1917),
+    ("1.14.1", 1957, 1920), ("1.14.2", 1963, 1922), ("1.14.3", 1968, 1926), ("1.14.4", 1976, 1929), ("1.15", 2225, 1950),
+    ("1.15.1", 2227, 1951), ("1.15.2", 2230, 2004), ("1.16", 2566, 2026), ("1.16.1", 2567, 2026), ("1.16.2", 2578, 2033),
+    ("1.16.3", 2580, 2037), ("1.16.4", 2584, 2044), ("1.16.5", 2586, 2044), ("1.17", 2724, 2123), ("1.17.1", 2730, 2127),
+    ("1.18", 2860, 2148), ("1.18.1", 2865, 2149), ("1.18.2", 2975, 2209), ("1.19", 3105, 2223), ("1.19.1", 3117, 2230),
+    ("1.19.2", 3120, 2231), ("1.19.3", 3218, 2249), ("1.19.4", 3337, 2311), ("1.20", 3463, 2323), ("1.20.1", 3465, 2324),
+    ("1.20.2", 3578, 2338), ("1.20.3", 3698, 2349), ("1.20.4", 3700, 2349), ("1.20.5", 3837, 2417), ("1.20.6", 3839, 2417),
+    ("1.21", 3953, 2424), ("1.21.1", 3955, 2424), ("1.21.2", 4080, 2443), ("1.21.3", 4082, 2443), ("1.21.4", 4189, 2449),
+    ("1.21.5", 4325, 2513), ("1.21.6", 4435, 2525), ("1.21.7", 4438, 2525), ("1.21.8", 4440, 2525),
+];
+
+/// The first snapshot to save a DataVersion
+const FIRST_DATA_VERSION: i32 = 100;
+
+/// The resource pack format of each release that changed it, it holds until the next one
+const PACK_FORMATS: &[(&str, u32)] = &[
+    ("1.6.1", 1), ("1.9", 2), ("1.11", 3), ("1.13", 4), ("1.15", 5), ("1.16.2", 6), ("1.17", 7), ("1.18", 8),
+    ("1.19", 9), ("1.19.3", 12), ("1.19.4", 13), ("1.20", 15), ("1.20.2", 18), ("1.20.3", 22), ("1.20.5", 32),
+    ("1.21", 34), ("1.21.2", 42), ("1.21.4", 46), ("1.21.5", 55), ("1.21.6", 63), ("1.21.7", 64),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LegacyPhase {
+    PreClassic,
+    Classic,
+    Indev,
+    Infdev,
+    Alpha,
+    Beta,
+}
+
+/// Ordered the way they come out in the lead up to a release
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum VersionKind {
+    Legacy(LegacyPhase),
+    Experimental,
+    Snapshot,
+    PreRelease,
+    ReleaseCandidate,
+    Release,
+}
+
+#[derive(Debug, Clone)]
+pub struct MinecraftVersion {
+    pub id: String,
+    pub kind: VersionKind,
+    /// The release this is, or leads up to. Versions from before 1.0 are all 0.0.0.
+    pub release: (u32, u32, u32),
+    /// What orders versions of the same kind leading up to the same release, e.g. the pre-release
+    /// number, or the year, week and letter of a snapshot
+    numbers: Vec<u32>,
+}
+
+impl MinecraftVersion {
+    /// Parse any official version id, e.g. "1.20.3", "1.20.3-pre2", "23w45a" or "b1.7.3"
+    pub fn parse(id: &str) -> Option<Self> {
+        let id = id.trim();
+        let lowercase = id.to_lowercase().replace(" - ", " ");
+
+        // releases, pre-releases and release candidates
+        static RELEASE_PATTERN: OnceLock<Regex> = OnceLock::new();
+        let release_pattern = RELEASE_PATTERN.get_or_init(|| Regex::new(r"^(\d+)\.(\d+)(?:\.(\d+))?(?:(?:-| )(pre|rc|pre-release |release candidate |snapshot-)(\d+))?$").unwrap());
+        if let Some(captures) = release_pattern.captures(&lowercase) {
+            let number = |i: usize| captures.get(i).map(|part| part.as_str().parse::<u32>().ok()).unwrap_or(Some(0));
+            let release = (number(1)?, number(2)?, number(3)?);
+            let (kind, numbers) = match captures.get(4).map(|part| part.as_str().trim()) {
+                Some("pre") | Some("pre-release") => (VersionKind::PreRelease, vec![number(5)?]),
+                Some("rc") | Some("release candidate") => (VersionKind::ReleaseCandidate, vec![number(5)?]),
+                Some("snapshot-") => (VersionKind::Snapshot, vec![number(5)?]),
+                _ => (VersionKind::Release, vec![]),
+            };
+            return Some(MinecraftVersion { id: String::from(id), kind, release, numbers });
+        }
+
+        // weekly snapshots, including the april fools ones like 24w14potato
+        static SNAPSHOT_PATTERN: OnceLock<Regex> = OnceLock::new();
+        let snapshot_pattern = SNAPSHOT_PATTERN.get_or_init(|| Regex::new(r"^(\d{2})w(\d{2})([a-z_]+)$").unwrap());
+        if let Some(captures) = snapshot_pattern.captures(&lowercase) {
+            let year = captures[1].parse::<u32>().ok()?;
+            let week = captures[2].parse::<u32>().ok()?;
+            let letter = match captures[3].as_bytes() {
+                [letter] if letter.is_ascii_lowercase() => (letter - b'a') as u32,
+                _ => 26,
+            };
+            return Some(MinecraftVersion {
+                id: String::from(id),
+                kind: VersionKind::Snapshot,
+                release: MinecraftVersion::snapshot_release(year * 100 + week),
+                numbers: vec![year, week, letter],
+            });
+        }
+
+        // experimental snapshots name the release they're a preview of
+        static EXPERIMENTAL_PATTERN: OnceLock<Regex> = OnceLock::new();
+        let experimental_pattern = EXPERIMENTAL_PATTERN.get_or_init(|| Regex::new(r"^(\d+)\.(\d+)(?:\.(\d+))?_[a-z_]*experimental[_-]snapshot-(\d+)$").unwrap());
+        if let Some(captures) = experimental_pattern.captures(&lowercase) {
+            let number = |i: usize| captures.get(i).map(|part| part.as_str().parse::<u32>().ok()).unwrap_or(Some(0));
+            return Some(MinecraftVersion {
+                id: String::from(id),
+                kind: VersionKind::Experimental,
+                release: (number(1)?, number(2)?, number(3)?),
+                numbers: vec![number(4)?],
+            });
+        }
+
+        // everything from before 1.0
+        static LEGACY_PATTERN: OnceLock<Regex> = OnceLock::new();
+        let legacy_pattern = LEGACY_PATTERN.get_or_init(|| Regex::new(r"^(rd-|c|in-|inf-|a|b)(\d[\d._a-z-]*)$").unwrap());
+        let captures = legacy_pattern.captures(&lowercase)?;
+        let phase = match &captures[1] {
+            "rd-" => LegacyPhase::PreClassic,
+            "c" => LegacyPhase::Classic,
+            "in-" => LegacyPhase::Indev,
+            "inf-" => LegacyPhase::Infdev,
+            "a" => LegacyPhase::Alpha,
+            _ => LegacyPhase::Beta,
+        };
+        static NUMBER_PATTERN: OnceLock<Regex> = OnceLock::new();
+        let numbers = NUMBER_PATTERN.get_or_init(|| Regex::new(r"\d+").unwrap())
+            .find_iter(&captures[2])
+            .filter_map(|number| number.as_str().parse::<u32>().ok())
+            .collect();
+
+        Some(MinecraftVersion { id: String::from(id), kind: VersionKind::Legacy(phase), release: (0, 0, 0), numbers })
+    }
+
+    /// The release a data version belongs to, snapshots count towards the release they lead up to
+    pub fn from_data_version(data_version: i32) -> Option<Self> {
+        if data_version < FIRST_DATA_VERSION { return None }
+
+        let (id, _, _) = RELEASES.iter()
+            .find(|(_, release_data_version, _)| data_version <= *release_data_version)?;
+        MinecraftVersion::parse(id)
+    }
+
+    pub fn is_release(&self) -> bool {
+        VersionKind::Release == self.kind
+    }
+
+    /// The data versions this version could have saved with. A release has exactly one, anything
+    /// leading up to a release falls between it and the release before. Versions newer than any
+    /// known release could be anything after the newest one.
+    pub fn data_versions(&self) -> Option<RangeInclusive<i32>> {
+        if let VersionKind::Legacy(_) = self.kind { return None }
+
+        let position = RELEASES.iter()
+            .position(|(id, _, _)| MinecraftVersion::release_numbers(id) == Some(self.release));
+        let (_, newest_data_version, _) = RELEASES[RELEASES.len() - 1];
+
+        let position = match position {
+            Some(position) => position,
+            None => {
+                return match RELEASES.iter().all(|(id, _, _)| MinecraftVersion::release_numbers(id) < Some(self.release)) {
+                    true => Some(newest_data_version + 1..=i32::MAX),
+                    false => None,
+                };
+            }
+        };
+
+        let (_, data_version, _) = RELEASES[position];
+        if 0 == data_version { return None }
+
+        if self.is_release() {
+            return Some(data_version..=data_version);
+        }
+
+        let previous_data_version = match position {
+            0 => FIRST_DATA_VERSION - 1,
+            position => RELEASES[position - 1].1.max(FIRST_DATA_VERSION - 1),
+        };
+        Some(previous_data_version + 1..=data_version - 1)
+    }
+
+    /// Whether something saved with the given data version could have been saved by this version
+    pub fn matches_data_version(&self, data_version: i32) -> bool {
+        match self.data_versions() {
+            Some(data_versions) => data_versions.contains(&data_version),
+            None => false,
+        }
+    }
+
+    /// The resource pack formats this version reads. Snapshots and pre-releases bump the format as
+    /// they go, so they get every format from the release before them to the one they lead up to.
+    pub fn pack_formats(&self) -> Option<RangeInclusive<u32>> {
+        if let VersionKind::Legacy(_) = self.kind { return None }
+
+        let format = MinecraftVersion::pack_format_of(self.release)?;
+        let newest_release = MinecraftVersion::release_numbers(RELEASES[RELEASES.len() - 1].0);
+        if Some(self.release) > newest_release {
+            return Some(format..=u32::MAX);
+        }
+        if self.is_release() {
+            return Some(format..=format);
+        }
+
+        let previous_format = RELEASES.iter().rev()
+            .filter_map(|(id, _, _)| MinecraftVersion::release_numbers(id))
+            .find(|release| release < &self.release)
+            .and_then(MinecraftVersion::pack_format_of)
+            .unwrap_or(format);
+        Some(previous_format..=format)
+    }
+
+    fn pack_format_of(release: (u32, u32, u32)) -> Option<u32> {
+        PACK_FORMATS.iter().rev()
+            .find(|(id, _)| MinecraftVersion::release_numbers(id) <= Some(release))
+            .map(|&(_, format)| format)
+    }
+
+    /// Snapshots lead up to the first release after the week they came out in. Ones newer than any
+    /// known release lead up to the release after the newest one.
+    fn snapshot_release(year_week: u32) -> (u32, u32, u32) {
+        for &(id, _, last_snapshot_week) in RELEASES {
+            if year_week <= last_snapshot_week {
+                return MinecraftVersion::release_numbers(id).unwrap();
+            }
+        }
+
+        let (major, minor, patch) = MinecraftVersion::release_numbers(RELEASES[RELEASES.len() - 1].0).unwrap();
+        (major, minor, patch + 1)
+    }
+
+    fn release_numbers(id: &str) -> Option<(u32, u32, u32)> {
+        let mut parts = id.split('.').map(|part| part.parse::<u32>().ok());
+        Some((parts.next()??, parts.next()??, parts.next().unwrap_or(Some(0))?))
+    }
+
+    fn sort_key(&self) -> ((u32, u32, u32), VersionKind, &Vec<u32>) {
+        (self.release, self.kind, &self.numbers)
+    }
+}
+
+impl PartialEq for MinecraftVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.sort_key() == other.sort_key()
+    }
+}
+
+impl Eq for MinecraftVersion {}
+
+impl PartialOrd for MinecraftVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MinecraftVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sort_key().cmp(&other.sort_key())
+    }
+}
+
+impl fmt::Display for MinecraftVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.id)
+    }
+}
@@ -0,0 +1,92 @@
+//! WORKER THREADS
+//!
+//! Decoding a chunk is decompression, NBT parsing and section unpacking, all of which is plain CPU
+//! work that doesn't depend on any other chunk. Jobs are handed out to a fixed number of scoped
+//! threads, each taking the next item as soon as it's done with the last, and the results come
+//! back in the same order as the input so the output matches decoding everything in a single thread.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// 0 means one thread per available core
+static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_threads(threads: usize) {
+    THREADS.store(threads, Ordering::Relaxed);
+}
+
+/// How many worker threads to use, always at least one
+pub fn threads() -> usize {
+    match THREADS.load(Ordering::Relaxed) {
+        0 => thread::available_parallelism().map(|cores| cores.get()).unwrap_or(1),
+        threads => threads,
+    }
+}
+
+/// Run a job over every item on the worker threads, returning the results in input order
+pub fn map_parallel<T, R, F>(items: Vec<T>, job: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let threads = threads().min(items.len());
+
+    // not worth spinning up threads for
+    if threads <= 1 {
+        return items.into_iter().map(job).collect();
+    }
+
+    let count = items.len();
+    let queue = Mutex::new(items.into_iter().enumerate());
+    let results: Mutex<Vec<Option<R>>> = Mutex::new((0..count).map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                // only hold the lock long enough to take the next item
+                let next = queue.lock().unwrap().next();
+                let (index, item) = match next {
+                    Some(next) => next,
+                    None => break,
+                };
+
+                let result = job(item);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results.into_inner().unwrap().into_iter().map(|result| result.unwrap()).collect()
+}
+
+/// Counts finished jobs across threads and prints every tenth of the way through
+pub struct Progress {
+    label: String,
+    total: usize,
+    done: AtomicUsize,
+}
+
+impl Progress {
+    pub fn new(label: &str, total: usize) -> Self {
+        Progress {
+            label: String::from(label),
+            total,
+            done: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn advance(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        if 0 == self.total { return }
+
+        if done * 10 / self.total != (done - 1) * 10 / self.total {
+            println!(" - {} {:?}/{:?}", self.label, done, self.total);
+        }
+    }
+
+    pub fn done(&self) -> usize {
+        self.done.load(Ordering::Relaxed)
+    }
+}
@@ -1,38 +1,71 @@
-use std::{
-    fs,
-    path::{
-        PathBuf,
-    },
-};
-use std::fs::DirEntry;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::io;
+use std::sync::{Arc, OnceLock};
 use std::process::exit;
-use regex::Regex;
+use crate::chunk::{Biomes, BlockState, Chunk, ChunkStatus, Structure};
+use crate::dimension::{Dimension, DimensionType};
+use crate::entity::*;
+use crate::layout::WorldLayout;
+use crate::launcher::JarLocator;
+use crate::level::Level;
+use crate::nbt::NBT;
+use crate::player::Player;
+use crate::poi::*;
 use crate::region::*;
+use crate::registry::BlockStateRegistry;
+use crate::source::join;
+use crate::workers::{map_parallel, Progress};
 
 
+/// How many regions lookups keep in memory before the least recently used unedited one is dropped
+const CACHED_REGIONS: usize = 16;
+
 #[derive(Debug)]
 pub struct World {
     pub world_path: String,
+    /// Where the world and each of its dimensions are read from, see layout.rs
+    pub layout: WorldLayout,
+    /// Every dimension with region files, the overworld's regions are also indexed below
+    pub dimensions: Vec<Dimension>,
+    /// Regions held in memory for lookups and edits, least recently used first, see load_region
+    /// and chunk_at. Everything else streams, entities and points of interest included.
     pub regions: Vec<Region>,
-    pub poi: Vec<u8>,
-    pub players: Vec<u8>,
-    pub entities: Vec<u8>,
-    pub level: Vec<u8>,
+    /// Region file paths by region x/z, nothing is decoded until it's asked for
+    pub region_index: BTreeMap<(i32, i32), String>,
+    pub players: Vec<Player>,
+    pub level: Level,
+    pub block_states: Arc<BlockStateRegistry>,
+    pub dimension_type: Arc<DimensionType>,
 }
 
 impl World {
     pub fn new(world_path: &str) -> World {
         println!("collecting world data from: {:?}", &world_path);
 
+        let layout = match WorldLayout::detect(world_path) {
+            Ok(layout) => layout,
+            Err(err) => {
+                eprintln!("Error opening world ({:?}): {err}", &world_path);
+                exit(44)
+            }
+        };
+
         let mut world = World {
             world_path: String::from(world_path),
-            level: vec![],
+            layout,
+            dimensions: vec![],
+            level: Level::new(),
             regions: vec![],
-            entities: vec![],
+            region_index: BTreeMap::new(),
             players: vec![],
-            poi: vec![],
+            block_states: Arc::new(BlockStateRegistry::new()),
+            dimension_type: Arc::new(DimensionType::overworld()),
         };
-        world.load_regions();
+        world.load_level();
+        world.load_dimensions();
+        world.index_regions();
+        world.load_players();
 
         println!("successfully loaded world data.");
 
@@ -40,167 +73,556 @@ impl World {
     }
 
     pub fn load_level(&mut self) {
-        todo!()
-    }
-
-    pub fn load_regions(&mut self) {
-        let mut region_path = PathBuf::from(&self.world_path);
-        let _ = region_path.push("region");
-        if !region_path.exists() || !region_path.is_dir() {
+        if !self.layout.source.is_file("level.dat") {
+            println!("no level.dat found in {:?}", &self.world_path);
             return;
         }
 
-        let mut region_files = vec![];
-        match fs::read_dir(region_path) {
-            Ok(results) => {
-                for result in results {
-                    region_files.push(result);
-                }
-            },
+        match self.layout.source.read("level.dat") {
+            Ok(bytes) => self.level = Level::from_nbt(NBT::from_bytes(bytes)),
             Err(err) => {
-                println!("Error reading region path: {:?}", err);
-                exit(42069)
+                eprintln!("Error reading level.dat: {err}");
+                exit(31)
             }
         }
+    }
 
-        let region_file_count = region_files.len();
-        let mut loading_count = 0;
+    /// Find the world's dimensions and work out how tall the overworld is, both of which depend on
+    /// the world's version and data packs
+    pub fn load_dimensions(&mut self) {
+        self.dimensions = self.layout.dimensions(self.level.data_version);
 
-        for region_file in region_files {
-            // counter
-            loading_count += 1;
-            println!("loading region {:?}/{:?}:", loading_count, region_file_count);
+        if let Some(dimension) = self.dimension("overworld") {
+            self.dimension_type = Arc::new(dimension.dimension_type.clone());
+        }
+
+        println!("found {:?} dimensions", self.dimensions.len());
+    }
+
+    /// A dimension by the name used in the config, e.g. "nether" or "mypack:mining"
+    pub fn dimension(&self, dimension: &str) -> Option<&Dimension> {
+        let directory = Dimension::directory(dimension);
+        self.dimensions.iter().find(|found| found.directory == directory)
+    }
 
-            // load the region
-            let dir_entry = *&region_file.as_ref().unwrap();
-            let file_path = dir_entry.path().to_string_lossy().to_string();
-            if file_path.ends_with(".mca") || file_path.ends_with(".mcr") {
-                let region = Region::new(&file_path);
-                let _ = self.regions.push(region);
+    /// List the region files (.mca/.mcr) found in the given subdirectory of the world
+    fn region_files(&self, subdir: &str) -> Vec<String> {
+        if !self.layout.source.is_dir(subdir) {
+            return vec![];
+        }
+
+        let mut region_files: Vec<String> = self.layout.source.list(subdir).into_iter()
+            .filter(|file_name| file_name.ends_with(".mca") || file_name.ends_with(".mcr"))
+            .filter(|file_name| Region::coordinates(file_name).is_some())
+            .map(|file_name| join(subdir, &file_name))
+            .collect();
+        self.layout.source.sort_for_reading(&mut region_files);
+
+        region_files
+    }
+
+    /// The indexed region files in the order they're cheapest to read in
+    fn indexed_region_files(&self) -> Vec<String> {
+        let mut region_files: Vec<String> = self.region_index.values().cloned().collect();
+        self.layout.source.sort_for_reading(&mut region_files);
+
+        region_files
+    }
+
+    /// Open a region file from the world without decoding anything yet
+    fn open_region(&self, file_path: &str) -> Region {
+        let mut region = Region::open(file_path, self.block_states.clone());
+        region.dimension_type = self.dimension_type.clone();
+        region.source = self.layout.source.clone();
+        region
+    }
+
+    /// Find the world's region files without reading any of them
+    pub fn index_regions(&mut self) {
+        self.region_index.clear();
+
+        for file_path in self.region_files("region") {
+            let Some((region_x, region_z)) = Region::coordinates(&file_path) else { continue };
+
+            // converted worlds keep their old McRegion files next to the Anvil ones, which win
+            match file_path.ends_with(".mca") {
+                true => { self.region_index.insert((region_x, region_z), file_path); }
+                false => { self.region_index.entry((region_x, region_z)).or_insert(file_path); }
             }
         }
+
+        println!("indexed {:?} regions", self.region_index.len());
     }
 
-    pub fn load_entities(&mut self) {
-        todo!()
+    /// Decode every region in turn, each one is dropped as soon as the iterator moves past it
+    pub fn iter_regions(&self) -> impl Iterator<Item = Region> + '_ {
+        self.indexed_region_files().into_iter().map(move |file_path| {
+            let mut region = self.open_region(&file_path);
+            region.load_chunks();
+            region
+        })
     }
 
-    pub fn load_players(&mut self) {
-        todo!()
+    /// Decode every generated chunk in the world one at a time, only the compressed payloads of
+    /// the current region are held in memory
+    pub fn iter_chunks(&self) -> impl Iterator<Item = Chunk> + '_ {
+        self.indexed_region_files().into_iter().flat_map(move |file_path| self.open_region(&file_path).into_chunks())
     }
 
-    pub fn load_poi(&mut self) {
-        todo!()
+    /// Decode every generated chunk of another dimension one at a time, from whichever folder the
+    /// dimension is kept in
+    pub fn iter_dimension_chunks<'a>(&'a self, dimension: &'a Dimension) -> impl Iterator<Item = Chunk> + 'a {
+        let region_path = dimension.region_directory();
+        let dimension_type = Arc::new(dimension.dimension_type.clone());
+
+        let mut region_files: Vec<String> = dimension.source.list(&region_path).into_iter()
+            .filter(|file_name| file_name.ends_with(".mca") && Region::coordinates(file_name).is_some())
+            .map(|file_name| join(&region_path, &file_name))
+            .collect();
+        dimension.source.sort_for_reading(&mut region_files);
+
+        region_files.into_iter()
+            .flat_map(move |file_path| {
+                let mut region = Region::open(&file_path, self.block_states.clone());
+                region.dimension_type = dimension_type.clone();
+                region.source = dimension.source.clone();
+                region.into_chunks()
+            })
     }
-}
 
+    /// Keep a region in memory for lookups and edits, decoding it if it isn't already loaded
+    pub fn load_region(&mut self, region_x: i32, region_z: i32) -> Option<&mut Region> {
+        let loaded = self.regions.iter()
+            .position(|region| region.region_x == region_x && region.region_z == region_z);
+
+        let position = match loaded {
+            Some(position) => position,
+            None => {
+                let file_path = self.region_index.get(&(region_x, region_z))?;
+                let mut region = self.open_region(file_path);
+                region.load_chunks();
+                self.regions.push(region);
+                self.regions.len() - 1
+            }
+        };
 
-pub trait DeepDirectoryDriver {
-    fn default_jar_path() -> String;
-}
+        Some(&mut self.regions[position])
+    }
 
-impl DeepDirectoryDriver for World {
-    fn default_jar_path() -> String {
-        // get default home directory
-        let mut install_path= dirs::home_dir().expect("Invalid home directory");
+    /// Load every indexed region into memory, only sensible for small worlds. The files are read
+    /// in turn, then spread over the workers, each decoding its own chunks, so the threads don't
+    /// multiply.
+    pub fn load_regions(&mut self) {
+        let missing: Vec<Region> = self.indexed_region_files().into_iter()
+            .map(|file_path| self.open_region(&file_path))
+            .filter(|region| !self.regions.iter()
+                .any(|loaded| loaded.region_x == region.region_x && loaded.region_z == region.region_z))
+            .map(|mut region| {
+                region.load_headers();
+                region
+            })
+            .collect();
+        let progress = Progress::new("loaded regions", missing.len());
+
+        let regions = map_parallel(missing, |mut region| {
+            region.chunks = region.chunk_payloads().into_iter()
+                .map(|(x, z, chunk_buffer)| Chunk::new(chunk_buffer, x, z, region.registry.clone(), region.dimension_type.clone()))
+                .collect();
+            progress.advance();
+            region
+        });
+
+        self.regions.extend(regions);
+    }
+
+    /// Drop the regions held in memory, edits that haven't been saved are lost
+    pub fn unload_regions(&mut self) {
+        self.regions.clear();
+    }
+
+    /// Decode the entity chunks of the whole world one entities/ region at a time
+    pub fn iter_entities(&self) -> impl Iterator<Item = EntityChunk> + '_ {
+        let region_files = self.region_files("entities");
+        let progress = Progress::new("read entity regions", region_files.len());
+
+        region_files.into_iter().flat_map(move |file_path| {
+            let entity_chunks = self.entity_chunks(&file_path);
+            progress.advance();
+            entity_chunks
+        })
+    }
+
+    /// Every entity chunk with entities in it of a single entities/ region file
+    fn entity_chunks(&self, file_path: &str) -> Vec<EntityChunk> {
+        // entity regions use the same layout as terrain regions, only the payload differs
+        let mut region = self.open_region(file_path);
+        region.load_headers();
+
+        region.chunk_payloads().into_iter()
+            .filter(|(_, _, chunk_buffer)| 0 < chunk_buffer.len())
+            .map(|(x, z, chunk_buffer)| EntityChunk::new(chunk_buffer, x, z))
+            .filter(|entity_chunk| 0 < entity_chunk.entities.len())
+            .collect()
+    }
+
+    /// The entities/ region file holding the given region, if there is one
+    fn entity_region(&self, region_x: i32, region_z: i32) -> Option<String> {
+        let file_path = join("entities", &format!("r.{}.{}.mca", region_x, region_z));
+        match self.layout.source.is_file(&file_path) {
+            true => Some(file_path),
+            false => None,
+        }
+    }
+
+    /// All entities stored in the chunk at the given chunk coordinates, only its region is read
+    pub fn entities_in_chunk(&self, chunk_x: i32, chunk_z: i32) -> Vec<Entity> {
+        let file_path = match self.entity_region(chunk_x >> 5, chunk_z >> 5) {
+            Some(file_path) => file_path,
+            None => return vec![],
+        };
+
+        self.entity_chunks(&file_path).into_iter()
+            .filter(|entity_chunk| entity_chunk.x == chunk_x && entity_chunk.z == chunk_z)
+            .flat_map(|entity_chunk| entity_chunk.entities)
+            .collect()
+    }
+
+    /// All entities within the box spanned by the two block coordinate corners, only the regions
+    /// overlapping the box are read
+    pub fn entities_in_box(&self, min: (f64, f64, f64), max: (f64, f64, f64)) -> Vec<Entity> {
+        let mut entities = vec![];
+
+        // skip every chunk that can't possibly overlap the box
+        let (min_chunk_x, min_chunk_z) = ((min.0.floor() as i32) >> 4, (min.2.floor() as i32) >> 4);
+        let (max_chunk_x, max_chunk_z) = ((max.0.floor() as i32) >> 4, (max.2.floor() as i32) >> 4);
 
-        // get windows-specific install path
-        if cfg!(target_os = "windows") {
-            for subdir in ["AppData", "Roaming", ".minecraft", "versions"] {
-                install_path.push(subdir);
+        for region_x in (min_chunk_x >> 5)..=(max_chunk_x >> 5) {
+            for region_z in (min_chunk_z >> 5)..=(max_chunk_z >> 5) {
+                let Some(file_path) = self.entity_region(region_x, region_z) else { continue };
+
+                for entity_chunk in self.entity_chunks(&file_path) {
+                    if entity_chunk.x < min_chunk_x || max_chunk_x < entity_chunk.x { continue }
+                    if entity_chunk.z < min_chunk_z || max_chunk_z < entity_chunk.z { continue }
+
+                    entities.extend(entity_chunk.entities.into_iter().filter(|entity| entity.within(min, max)));
+                }
             }
         }
 
-        // get linux-specific install path
-        if cfg!(target_os = "linux") {
-            for subdir in [".minecraft", "versions"] {
-                install_path.push(subdir);
+        entities
+    }
+
+    pub fn load_players(&mut self) {
+        if !self.layout.source.is_dir("playerdata") {
+            return;
+        }
+
+        // the user cache lives in the server directory, one level up from the world, which a
+        // backup won't have
+        let mut names = HashMap::new();
+        if let Some(server_path) = self.layout.source.local_path("").and_then(|world_path| world_path.parent().map(Path::to_path_buf)) {
+            let cache_path = server_path.join("usercache.json");
+            if cache_path.exists() {
+                names.extend(Player::load_user_cache(&cache_path));
             }
         }
+        if let Ok(bytes) = self.layout.source.read("usercache.json") {
+            names.extend(Player::parse_user_cache(&String::from_utf8_lossy(&bytes)));
+        }
+
+        for file_name in self.layout.source.list("playerdata") {
+            // skip the .dat_old backups and anything else that isn't player data
+            if !file_name.ends_with(".dat") { continue }
+
+            let Some(mut player) = Player::load(&self.layout.source, &join("playerdata", &file_name)) else { continue };
+            player.name = names.get(&player.uuid.to_lowercase()).cloned();
+            self.players.push(player);
+        }
+
+        println!("loaded {:?} players", &self.players.len());
+    }
+
+    /// Read the points of interest of the whole world one poi/ region at a time
+    pub fn iter_poi(&self) -> impl Iterator<Item = PointOfInterest> + '_ {
+        let region_files = self.region_files("poi");
+        let progress = Progress::new("read poi regions", region_files.len());
+
+        region_files.into_iter().flat_map(move |file_path| {
+            let mut region = self.open_region(&file_path);
+            region.load_headers();
+
+            let records: Vec<PointOfInterest> = region.chunk_payloads().into_iter()
+                .flat_map(|(_, _, chunk_buffer)| PointOfInterest::from_chunk(chunk_buffer))
+                .collect();
+            progress.advance();
+            records
+        })
+    }
+
+    /// Rank every loaded chunk by its number of pending block and fluid ticks, busiest first
+    pub fn tick_report(&self) -> Vec<TickReport> {
+        let mut reports = vec![];
 
-        // get macos-specific install path
-        if cfg!(target_os = "macos") {
-            for subdir in ["Library", "Application Support", "minecraft", "versions"] {
-                install_path.push(subdir);
+        for chunk in self.iter_chunks() {
+            let pending = chunk.block_ticks.len() + chunk.fluid_ticks.len();
+            if 0 == pending { continue }
+
+            // tally ticks by the block or fluid they belong to
+            let mut counts: HashMap<String, usize> = HashMap::new();
+            for tile_tick in chunk.block_ticks.iter().chain(chunk.fluid_ticks.iter()) {
+                *counts.entry(tile_tick.i.to_string()).or_insert(0) += 1;
             }
+
+            let mut by_type: Vec<(String, usize)> = counts.into_iter().collect();
+            by_type.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+            reports.push(TickReport {
+                chunk_x: chunk.x_pos,
+                chunk_z: chunk.z_pos,
+                block_ticks: chunk.block_ticks.len(),
+                fluid_ticks: chunk.fluid_ticks.len(),
+                by_type,
+            });
         }
 
-        // read directory for installed versions
-        let entries = match fs::read_dir(&install_path) {
-            Ok(results) => { results }
-            Err(err) => {
-                println!("Error reading directory: {err}\n{:?}", &install_path);
-                exit(38);
+        reports.sort_by(|a, b| b.total().cmp(&a.total()));
+
+        reports
+    }
+
+    /// The chunk containing the given block coordinates, loading its region on demand. Only the
+    /// most recently used regions stay loaded, edited ones are saved when they're dropped.
+    pub fn chunk_at(&mut self, x: i32, z: i32) -> Option<&Chunk> {
+        self.cache_region(x >> 9, z >> 9);
+        self.loaded_chunk(x, z)
+    }
+
+    /// Load a region for a lookup, making room by dropping the least recently used region. At most
+    /// CACHED_REGIONS stay loaded, however many were edited, unless the world is read only.
+    fn cache_region(&mut self, region_x: i32, region_z: i32) {
+        let loaded = self.regions.iter()
+            .position(|region| region.region_x == region_x && region.region_z == region_z);
+
+        match loaded {
+            // the front of the list is what gets dropped first
+            Some(position) => {
+                let region = self.regions.remove(position);
+                self.regions.push(region);
             }
-        };
+            None => {
+                if CACHED_REGIONS <= self.regions.len() {
+                    self.evict_region();
+                }
+                self.load_region(region_x, region_z);
+            }
+        }
+    }
 
-        let pattern = Regex::new(r"^\d+\.\d+(\.\d)?$").expect("the unexpected");
-        let mut newest = String::from("0.0.0");
+    /// Drop the least recently used region, saving it first if it was edited. Regions that can't
+    /// be saved, like those of a world read from a backup, are kept and the next one is tried.
+    fn evict_region(&mut self) {
+        for position in 0..self.regions.len() {
+            if self.regions[position].is_modified() {
+                if let Err(err) = self.save_region(position) {
+                    log::warn!("keeping edited region {:?} in memory, it couldn't be saved: {err}", &self.regions[position].region_path);
+                    continue;
+                }
+            }
 
-        for entry in entries {
-            // filter result
-            let entry: DirEntry = entry.expect("the unexpected");
+            self.regions.remove(position);
+            return;
+        }
+    }
 
-            /*
-             how do I check if it's a file or directory?
-             */
+    /// The chunk containing the given block coordinates, if its region is already loaded
+    fn loaded_chunk(&self, x: i32, z: i32) -> Option<&Chunk> {
+        let (chunk_x, chunk_z) = (x >> 4, z >> 4);
 
-            // get filename
-            let filename = String::from(entry.file_name().to_str().unwrap());
+        self.regions.iter()
+            .filter(|region| region.region_x == chunk_x >> 5 && region.region_z == chunk_z >> 5)
+            .flat_map(|region| region.chunks.iter())
+            .find(|chunk| chunk.x_pos == chunk_x && chunk.z_pos == chunk_z)
+    }
+
+    /// The chunk containing the given block coordinates, loading its region if needed
+    pub fn chunk_at_mut(&mut self, x: i32, z: i32) -> Option<&mut Chunk> {
+        self.cache_region(x >> 9, z >> 9);
+        self.load_region(x >> 9, z >> 9)?.chunk_at_mut(x, z)
+    }
 
-            // exclude not-applicable subdirs via fancy pancy rejular expression
-            newest = match pattern.is_match(&filename) {
-                // weird string trickery to do magic with numbers
-                true => { World::newer_version(&newest, &filename) }
-                false => { String::from(&newest) }
-            };
+    /// Place a block state at the given block coordinates. The edit stays in memory until save is
+    /// called or its region is dropped to make room for another, which saves it.
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block_state: BlockState) {
+        if let Some(chunk) = self.chunk_at_mut(x, z) {
+            chunk.set_block(x, y, z, block_state);
         }
+    }
 
-        install_path.push(&newest);
-        newest.push_str(".jar");
-        install_path.push(&newest);
+    pub fn set_biome(&mut self, x: i32, y: i32, z: i32, biome: &str) {
+        if let Some(chunk) = self.chunk_at_mut(x, z) {
+            chunk.set_biome(x, y, z, biome);
+        }
+    }
+
+    /// Write every region holding an edited chunk back to where it was loaded from, returning how
+    /// many regions were saved. Worlds read from a backup archive can't be saved.
+    pub fn save(&mut self) -> io::Result<usize> {
+        let mut saved = 0;
+        for position in 0..self.regions.len() {
+            if !self.regions[position].is_modified() { continue }
+            self.save_region(position)?;
+            saved += 1;
+        }
 
-        String::from(install_path.to_str().unwrap())
+        Ok(saved)
     }
-}
 
-pub trait Versioning {
-    fn newer_version(ver_1: &str, ver_2: &str) -> String;
-    fn version_int(version_number: &str) -> usize;
-}
+    /// Write a loaded region back to where it was loaded from
+    fn save_region(&mut self, position: usize) -> io::Result<()> {
+        let region = &mut self.regions[position];
+        let region_path = match self.layout.source.local_path(&region.region_path) {
+            Some(region_path) => region_path,
+            None => return Err(io::Error::new(io::ErrorKind::Unsupported, "worlds read from a backup are read only")),
+        };
+
+        region.save(&region_path.to_string_lossy())
+    }
+
+    pub fn biome_at(&mut self, x: i32, y: i32, z: i32) -> Option<&str> {
+        self.chunk_at(x, z)?.biome_at(x, y, z)
+    }
+
+    pub fn biome_column(&mut self, x: i32, z: i32) -> impl Iterator<Item = (i32, &str)> + '_ {
+        self.chunk_at(x, z).into_iter().flat_map(move |chunk| chunk.biome_column(x, z))
+    }
+
+    /// The biome at the given block coordinates blended the way the client does it for tinting,
+    /// looking into neighbouring chunks where needed
+    pub fn smoothed_biome_at(&mut self, x: i32, y: i32, z: i32) -> Option<&str> {
+        let (cell_x, cell_y, cell_z) = Biomes::zoom(self.level.biome_zoom_seed, x, y, z);
+        let (blended_x, blended_y, blended_z) = (cell_x * 4, cell_y * 4, cell_z * 4);
 
-impl Versioning for World {
-    fn newer_version(ver_1: &str, ver_2: &str) -> String {
-        let val_1 = World::version_int(&ver_1);
-        let val_2 = World::version_int(&ver_2);
+        // both regions are loaded up front so the lookups below only need to borrow
+        self.cache_region(blended_x >> 9, blended_z >> 9);
+        self.cache_region(x >> 9, z >> 9);
 
-        match val_1 < val_2 {
-            true => String::from(ver_2),
-            false => String::from(ver_1)
+        match self.loaded_chunk(blended_x, blended_z).and_then(|chunk| chunk.biome_at(blended_x, blended_y, blended_z)) {
+            Some(biome) => Some(biome),
+            // fall back to the unblended biome at the top and bottom of the world
+            None => self.loaded_chunk(x, z)?.biome_at(x, y, z),
         }
     }
 
-    fn version_int(version_number: &str) -> usize {
-        let parts: Vec<&str> = version_number.split(".").collect();
-        let mut output = 0;
+    /// The block and sky light at the given block coordinates, None where no chunk was generated.
+    /// Chunks saved without light get it worked out the first time it's asked for.
+    pub fn light_at(&mut self, x: i32, y: i32, z: i32) -> Option<(u8, u8)> {
+        let chunk = self.chunk_at_mut(x, z)?;
+        if chunk.needs_light() {
+            chunk.relight();
+        }
 
-        match parts.clone().into_iter().count() {
-            3 => {
-                // minor releases, A.B.C
-                output += parts[0].parse::<usize>().unwrap() << 16;
-                output += parts[1].parse::<usize>().unwrap() << 8;
-                output += parts[2].parse::<usize>().unwrap();
-            }
-            2 => {
-                // major releases, A.B
-                output += parts[0].parse::<usize>().unwrap() << 16;
-                output += parts[1].parse::<usize>().unwrap() << 8;
-            }
-            _ => {
-                // invalid
+        Some(chunk.light_at(x, y, z))
+    }
+
+    /// Per chunk generation details for a debug overlay: where old terrain is blended into new, how
+    /// many blocks are waiting on post-processing and how much has been carved out
+    pub fn debug_overlay(&self) -> Vec<ChunkDebug> {
+        let mut overlay: Vec<ChunkDebug> = self.iter_chunks()
+            .map(|chunk| ChunkDebug {
+                chunk_x: chunk.x_pos,
+                chunk_z: chunk.z_pos,
+                status: chunk.status,
+                blending: chunk.blending_data.is_some(),
+                seam: false,
+                post_processing: chunk.post_processing.len(),
+                carved: chunk.carving_masks.carved_count(),
+            })
+            .collect();
+
+        let blending: HashMap<(i32, i32), bool> = overlay.iter()
+            .map(|chunk| ((chunk.chunk_x, chunk.chunk_z), chunk.blending))
+            .collect();
+
+        // a seam is where a blended chunk meets one that isn't
+        for chunk in overlay.iter_mut().filter(|chunk| chunk.blending) {
+            chunk.seam = [(-1, 0), (1, 0), (0, -1), (0, 1)].iter().any(|(dx, dz)| {
+                Some(&false) == blending.get(&(chunk.chunk_x + dx, chunk.chunk_z + dz))
+            });
+        }
+
+        overlay
+    }
+
+    /// Every structure start in the world grouped by category, e.g. "village", "monument",
+    /// "stronghold" or "ancient_city"
+    pub fn structure_index(&self) -> HashMap<String, Vec<Structure>> {
+        let mut index: HashMap<String, Vec<Structure>> = HashMap::new();
+
+        for chunk in self.iter_chunks() {
+            for structure in chunk.structures {
+                index.entry(structure.category()).or_insert(vec![]).push(structure);
             }
         }
 
-        output
+        index
+    }
+
+    /// Every point of interest of the given type, e.g. "minecraft:nether_portal"
+    pub fn poi_of_type(&self, poi_type: &str) -> Vec<PointOfInterest> {
+        self.iter_poi().filter(|poi| poi.poi_type == poi_type).collect()
+    }
+
+    /// Every bed a villager has claimed as its home
+    pub fn claimed_beds(&self) -> Vec<PointOfInterest> {
+        self.poi_of_type("minecraft:home").into_iter().filter(|poi| poi.is_claimed()).collect()
+    }
+}
+
+
+#[derive(Debug)]
+pub struct TickReport {
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+    pub block_ticks: usize,
+    pub fluid_ticks: usize,
+    pub by_type: Vec<(String, usize)>,
+}
+
+impl TickReport {
+    pub fn total(&self) -> usize {
+        self.block_ticks + self.fluid_ticks
+    }
+}
+
+#[derive(Debug)]
+pub struct ChunkDebug {
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+    pub status: ChunkStatus,
+    pub blending: bool,
+    pub seam: bool,
+    pub post_processing: usize,
+    pub carved: usize,
+}
+
+pub trait DeepDirectoryDriver {
+    fn default_jar_path() -> String;
+}
+
+impl DeepDirectoryDriver for World {
+    fn default_jar_path() -> String {
+        // looking through every launcher install is slow, so it's only done the once
+        static DEFAULT_JAR: OnceLock<String> = OnceLock::new();
+        DEFAULT_JAR.get_or_init(|| {
+            // without a world to match, the newest release any launcher has installed
+            let locator = JarLocator::discover(&[]);
+            match locator.select(None, &[]) {
+                Some(candidate) => String::from(candidate.path.to_string_lossy()),
+                None => {
+                    println!("Couldn't find a minecraft jar in any launcher install");
+                    exit(38);
+                }
+            }
+        }).clone()
     }
-}
\ No newline at end of file
+}